@@ -41,7 +41,11 @@ pub fn app() {
         .get_matches();
 
     match matches.subcommand() {
-        ("build", Some(matches)) => build(matches.is_present("then-serve")),
+        ("build", Some(matches)) => {
+            if build(matches.is_present("then-serve")).is_err() {
+                std::process::exit(1);
+            }
+        }
         ("new", Some(matches)) => {
             new(matches.value_of("site_name").unwrap());
         }
@@ -49,7 +53,7 @@ pub fn app() {
             matches.value_of("port").unwrap(),
             matches.is_present("no-watch"),
         ),
-        ("watch", Some(_)) => watch(),
+        ("watch", Some(_)) => watch(None),
         ("", None) => {
             println!("No subcommand was used, 'scroll -h' or 'scroll --help' for more information.")
         }
@@ -1,208 +1,54 @@
-use serde::{Deserialize, Serialize};
-use std::collections::hash_set::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use std::{fs, fs::File};
-use walkdir::{DirEntry, WalkDir};
-
-use crate::css_gen;
 use crate::defaults;
-use crate::sop;
-use sop::ast_gen::OrgDoc;
-use sop::parser::OrgParser;
+use crate::error::ScrollError;
+use crate::live_reload::{LiveReload, RELOAD_PATH};
+use crate::site::{Change, Site};
 use std::io::prelude::*;
-use tinytemplate::TinyTemplate;
-
-lazy_static! {
-    static ref BLACK_LIST: Vec<&'static str> =
-        vec!["templates", "style_config.toml", "public", "scroll.toml"];
-    static ref SITE_STYLES: HashSet<String> = HashSet::new();
-    static ref SCROLL_CONFIG: ScrollConfig = {
-        if let Ok(s) = std::fs::read_to_string("scroll.toml") {
-            if let Ok(t) = toml::from_str(&s) {
-                return t;
-            }
-            println!("Error while parsing scroll.toml. Scroll will use default config.");
-        } else {
-            println!("Error while reading scroll.toml. Scroll will use default config.");
-        }
-
-        toml::from_str(&defaults::CONF).unwrap()
-    };
-    static ref TEMPLATE: String = {
-        if let Ok(s) =
-            std::fs::read_to_string(format!("templates/{}", SCROLL_CONFIG.default_template))
-        {
-            return s;
-        } else {
-            println!(
-                "Error while reading {}. Scroll will use default template.",
-                SCROLL_CONFIG.default_template
-            );
-        }
-
-        defaults::TEMPLATE.to_string()
-    };
-}
 
-#[derive(Debug, Deserialize)]
-struct ScrollConfig {
-    default_template: String,
-}
-
-#[derive(Serialize)]
-struct Page {
-    title: String,
-    summary: String,
-    date: String,
-}
-impl Page {
-    fn new(ast: OrgDoc) -> Page {
-        Page {
-            title: ast.title,
-            summary: ast.summary,
-            date: ast.date,
-        }
-    }
-}
-
-pub fn build(is_serve: bool) {
-    if !fs::metadata("./scroll.toml").is_ok() {
-        println!("No config file detected!\n scroll.toml file is required in scroll project root for site generation.");
-        return;
-    }
-    match fs::remove_dir_all("./public") {
-        Err(_) => (), //println!("Error while tring to delete old site. Error: {}", e),
-        Ok(_) => (),  //println!("Older site deleted!"),
+pub fn build(is_serve: bool) -> Result<(), Vec<ScrollError>> {
+    if fs::metadata("./scroll.toml").is_err() {
+        let errors = vec![ScrollError::Config {
+            path: PathBuf::from("scroll.toml"),
+            line: None,
+            msg: "No config file detected! scroll.toml file is required in scroll project root for site generation.".to_string(),
+        }];
+        report_errors(&errors);
+        return Err(errors);
     }
     println!("Building..");
 
-    let mut site_styles: HashSet<String> = HashSet::new();
+    let mut site = Site::new();
+    let errors = site.build();
+    report_warnings(&site.take_warnings());
 
-    for entry in WalkDir::new(".").into_iter().filter_entry(|e| !is_bl(e)) {
-        if let Ok(e) = entry {
-            if e.file_name().to_str().unwrap().ends_with(".org") {
-                create_html(e.path(), &mut site_styles);
-            } else {
-                copy_file_to_site(e.path());
-            }
-        }
-    }
-
-    if let Ok(mut f) = File::create("./public/scroll_style.css") {
-        f.write_all(css_gen::css_generator::generate_site_styles(site_styles).as_bytes())
-            .unwrap();
-    }
-
-    fn is_bl(entry: &DirEntry) -> bool {
-        entry
-            .file_name()
-            .to_str()
-            .map(|s| {
-                if s == "." {
-                    return false;
-                } else {
-                    BLACK_LIST.contains(&s) || s.starts_with(".")
-                }
-            })
-            .unwrap_or(false)
-    }
-
-    fn handle_site_path(path: &std::path::Path, is_org: bool) -> Option<String> {
-        let mut new_path = String::from("./public/");
-        if let Some(p_str) = path.to_str() {
-            if is_org {
-                if let Some(p) = p_str.get(2..p_str.len() - 4) {
-                    new_path.push_str(p);
-                    new_path.push_str(".html");
-                } else {
-                    return None;
-                }
-            } else {
-                if let Some(p) = p_str.get(2..) {
-                    new_path.push_str(p);
-                } else {
-                    return None;
-                }
-            }
-        } else {
-            return None;
-        }
-
-        if let Ok(md) = fs::metadata(&path) {
-            if md.is_dir() {
-                fs::create_dir_all(&new_path).unwrap();
-            } else {
-                fs::create_dir_all(new_path.get(..new_path.rfind("/").unwrap()).unwrap()).unwrap();
-            }
-        }
-
-        Some(new_path)
-    }
+    let result = if errors.is_empty() {
+        println!("Site builded under 'public' folder!\n");
+        Ok(())
+    } else {
+        report_errors(&errors);
+        Err(errors)
+    };
 
-    fn copy_file_to_site(path: &std::path::Path) {
-        if let Some(p) = handle_site_path(path, false) {
-            if let Ok(md) = fs::metadata(&path) {
-                if md.is_dir() {
-                    return;
-                }
-            }
-            if let Err(e) = fs::copy(&path, &p) {
-                println!(
-                    "Can't copy file from: {}, to: {}, skipping. Error: {}",
-                    &path.to_str().unwrap(),
-                    &p,
-                    e
-                );
-            }
-        }
+    if is_serve {
+        serve("1919", false);
     }
+    result
+}
 
-    fn create_html(path: &std::path::Path, site_styles: &mut HashSet<String>) {
-        let ast = OrgParser::create_from_path(path).create_ast();
-
-        for style in &ast.styles {
-            site_styles.insert(style.to_string());
-        }
-
-        let page_html = OrgParser::generate_html(&ast.ast);
-        let page_template: String;
-
-        if let Some(t) = &ast.template {
-            if let Ok(s) = std::fs::read_to_string(format!("templates/{}", t)) {
-                page_template = s;
-            } else {
-                println!(
-                    "Error while reading this template: {}, scroll will use default template",
-                    t
-                );
-                page_template = TEMPLATE.to_string();
-            }
-        } else {
-            page_template = TEMPLATE.to_string();
-        }
-
-        let mut tt = TinyTemplate::new();
-        tt.add_template("tmp", &page_template).unwrap();
-
-        let page = Page::new(ast);
-
-        let rendered = tt
-            .render("tmp", &page)
-            .unwrap()
-            .replace("<<page>>", &page_html);
-
-        if let Some(p) = handle_site_path(path, true) {
-            File::create(p)
-                .unwrap()
-                .write_all(rendered.as_bytes())
-                .unwrap();
-        }
+fn report_errors(errors: &[ScrollError]) {
+    println!("\nBuild failed with {} error(s):", errors.len());
+    for e in errors {
+        println!("  {}", e);
     }
+    println!();
+}
 
-    println!("Site builded under 'public' folder!\n");
-
-    if is_serve {
-        serve("1919", false);
+fn report_warnings(warnings: &[ScrollError]) {
+    for w in warnings {
+        println!("warning: {}", w);
     }
 }
 
@@ -280,18 +126,76 @@ pub fn serve(port: &str, no_watch: bool) {
     let host = "127.0.0.1";
     let p = port.to_owned();
 
-    let mut server = simple_server::Server::new(|request, mut response| {
+    // every open page keeps a long-poll request waiting on the live reload
+    // endpoint, so the default pool of one thread per cpu is not enough.
+    if std::env::var("SIMPLESERVER_THREADS").is_err() {
+        std::env::set_var("SIMPLESERVER_THREADS", "32");
+    }
+
+    let live_reload = if no_watch {
+        None
+    } else {
+        Some(Arc::new(LiveReload::new()))
+    };
+    let server_reload = live_reload.clone();
+
+    let mut server = simple_server::Server::new(move |request, mut response| {
         match (request.method(), request.uri().path()) {
             (&simple_server::Method::GET, "/") => {
                 response.status(simple_server::StatusCode::MOVED_PERMANENTLY);
                 response.header("Location", "/index.html");
                 Ok(response.body(Vec::new())?)
             }
+            (&simple_server::Method::GET, RELOAD_PATH) if server_reload.is_some() => {
+                let since = request
+                    .uri()
+                    .query()
+                    .and_then(|q| q.strip_prefix("v="))
+                    .and_then(|v| v.parse::<u64>().ok());
+                response.header("Content-Type", "application/json");
+                response.header("Cache-Control", "no-store");
+                Ok(response.body(server_reload.as_ref().unwrap().wait(since).into_bytes())?)
+            }
+            (&simple_server::Method::GET, path) => {
+                let fs_path = Path::new(&path[1..]);
+                let is_traversal = fs_path
+                    .components()
+                    .any(|c| !matches!(c, std::path::Component::Normal(_)));
+                let fs_path = Path::new("public").join(fs_path);
+                if is_traversal || !fs_path.is_file() {
+                    response.status(simple_server::StatusCode::NOT_FOUND);
+                    return Ok(response.body("<h1>404</h1><p>Not found!<p>".as_bytes().to_vec())?);
+                }
+
+                let mut body = fs::read(&fs_path)?;
+                match fs_path.extension().and_then(|e| e.to_str()) {
+                    Some("html") => {
+                        response.header("Content-Type", "text/html; charset=utf-8");
+                        if server_reload.is_some() {
+                            body = LiveReload::inject_script(&String::from_utf8_lossy(&body))
+                                .into_bytes();
+                        }
+                    }
+                    Some("css") => {
+                        response.header("Content-Type", "text/css");
+                    }
+                    Some("js") => {
+                        response.header("Content-Type", "application/javascript");
+                    }
+                    Some("svg") => {
+                        response.header("Content-Type", "image/svg+xml");
+                    }
+                    _ => (),
+                }
+                Ok(response.body(body)?)
+            }
             (_, _) => Ok(response.body(Vec::new())?),
         }
     });
 
-    server.set_static_directory("public");
+    // static files are served by the handler above, so html can get the
+    // live reload script.
+    server.dont_serve_static_files();
     use std::thread;
 
     let server = thread::spawn(move || {
@@ -302,35 +206,68 @@ pub fn serve(port: &str, no_watch: bool) {
     println!("Go \"127.0.0.1:{}\"\n", port);
     if !no_watch {
         let watcher = thread::spawn(move || {
-            watch();
+            watch(live_reload);
         });
         watcher.join().unwrap();
     }
     server.join().unwrap();
 }
 
-use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use notify::{DebouncedEvent, RecommendedWatcher, RecursiveMode, Watcher};
 use std::time::Duration;
 
-pub fn watch() {
+pub fn watch(live_reload: Option<Arc<LiveReload>>) {
     let (tx, rx) = std::sync::mpsc::channel();
 
     let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(1)).unwrap();
     watcher.watch("./", RecursiveMode::Recursive).unwrap();
 
+    let mut site = Site::new();
+    let errors = site.scan();
+    report_warnings(&site.take_warnings());
+    if !errors.is_empty() {
+        report_errors(&errors);
+    }
+
     println!("Whatching files..",);
     loop {
-        match rx.recv() {
-            Ok(event) => {
-                if let notify::DebouncedEvent::Write(path) = event {
-                    if let Some(s) = path.to_str() {
-                        if !s.contains("/public") {
-                            build(false);
-                        }
-                    }
+        let (path, (change, errors)) = match rx.recv() {
+            Ok(DebouncedEvent::Write(path))
+            | Ok(DebouncedEvent::Create(path))
+            | Ok(DebouncedEvent::Chmod(path)) => {
+                let result = site.update(&path);
+                (path, result)
+            }
+            Ok(DebouncedEvent::Remove(path)) => {
+                let result = site.remove(&path);
+                (path, result)
+            }
+            Ok(DebouncedEvent::Rename(from, to)) => {
+                let (removed, mut errors) = site.remove(&from);
+                let (updated, mut update_errors) = site.update(&to);
+                errors.append(&mut update_errors);
+                (
+                    to,
+                    (if removed > updated { removed } else { updated }, errors),
+                )
+            }
+            Ok(_) => continue,
+            Err(e) => {
+                println!("watch error: {:?}", e);
+                continue;
+            }
+        };
+
+        report_warnings(&site.take_warnings());
+        if errors.is_empty() {
+            if change != Change::Nothing {
+                println!("Rebuilt {}", path.display());
+                if let Some(l) = &live_reload {
+                    l.notify(change == Change::Css);
                 }
             }
-            Err(e) => println!("watch error: {:?}", e),
+        } else {
+            report_errors(&errors);
         }
     }
 }
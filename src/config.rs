@@ -0,0 +1,78 @@
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::defaults;
+use crate::error::ScrollError;
+
+#[derive(Debug, Deserialize)]
+pub struct ScrollConfig {
+    pub default_template: String,
+    // strftime format of `date` as shown by templates, see
+    // https://docs.rs/chrono/latest/chrono/format/strftime/
+    #[serde(default = "default_date_format")]
+    pub date_format: String,
+    // syntect theme of `highlight.css`, e.g. "base16-ocean.dark"
+    #[serde(default = "default_highlight_theme")]
+    pub highlight_theme: String,
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub feed: Option<FeedConfig>,
+    #[serde(default)]
+    pub sitemap: bool,
+}
+
+// `[feed]` table of scroll.toml, a feed is written for each of `dirs`.
+#[derive(Debug, Deserialize)]
+pub struct FeedConfig {
+    #[serde(default)]
+    pub dirs: Vec<String>,
+    #[serde(default = "default_true")]
+    pub rss: bool,
+    #[serde(default)]
+    pub atom: bool,
+    #[serde(default)]
+    pub full_content: bool,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+fn default_date_format() -> String {
+    "%Y-%m-%d".to_string()
+}
+
+fn default_highlight_theme() -> String {
+    "InspiredGitHub".to_string()
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ScrollConfig {
+    fn default() -> Self {
+        toml::from_str(&defaults::CONF).unwrap()
+    }
+}
+
+impl ScrollConfig {
+    pub fn load() -> Result<ScrollConfig, ScrollError> {
+        let path = Path::new("scroll.toml");
+        let s = std::fs::read_to_string(path).map_err(|e| ScrollError::io(path, e))?;
+        let config: ScrollConfig =
+            toml::from_str(&s).map_err(|e| ScrollError::from_toml(path, e))?;
+        if StrftimeItems::new(&config.date_format).any(|i| i == Item::Error) {
+            return Err(ScrollError::Config {
+                path: path.to_path_buf(),
+                line: None,
+                msg: format!("invalid date_format \"{}\"", config.date_format),
+            });
+        }
+        Ok(config)
+    }
+}
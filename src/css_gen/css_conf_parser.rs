@@ -1,21 +1,18 @@
 use crate::defaults::CSS_DEFAULT;
+use crate::error::ScrollError;
 use serde::Deserialize;
+use std::path::Path;
 
-lazy_static! {
-    pub static ref CONFIG: Config = {
-        if let Ok(s) = std::fs::read_to_string("style_config.toml") {
-            match toml::from_str(&s) {
-                Ok(t) => return t,
-                Err(e) => println!(
-                    "Error while parsing style_config.toml. Scroll will use default config for this time. Error: {}", e
-                ),
-            }
-        } else {
-            println!("Error while reading style_config.toml. Scroll will use default config.");
-        }
+pub fn load_config() -> Result<Config, ScrollError> {
+    let path = Path::new("style_config.toml");
+    let s = std::fs::read_to_string(path).map_err(|e| ScrollError::io(path, e))?;
+    toml::from_str(&s).map_err(|e| ScrollError::from_toml(path, e))
+}
 
+impl Default for Config {
+    fn default() -> Self {
         toml::from_str(&CSS_DEFAULT).unwrap()
-    };
+    }
 }
 
 #[derive(Debug, Deserialize)]
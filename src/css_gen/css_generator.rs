@@ -1,17 +1,33 @@
-use super::css_conf_parser::{Property, CONFIG};
+use super::css_conf_parser::{Config, Property};
 use std::collections::hash_map::HashMap;
 use std::collections::hash_set::HashSet;
 
-pub fn generate_site_styles(site_styles: HashSet<String>) -> String {
+#[derive(Debug)]
+pub struct StyleError {
+    pub style: String,
+    pub msg: String,
+}
+
+pub fn generate_site_styles(
+    site_styles: HashSet<String>,
+    config: &Config,
+) -> (String, Vec<StyleError>) {
     let mut style = String::new();
+    let mut errors = Vec::new();
     let mut all_css: HashMap<String, Vec<String>> = HashMap::new();
     for class_name in site_styles {
-        if let Some((key, value)) = handle_css(&class_name) {
-            if let Some(v) = all_css.get_mut(&key) {
-                v.push(value);
-            } else {
-                all_css.insert(key, vec![value]);
+        match handle_css(&class_name, config) {
+            Ok((key, value)) => {
+                if let Some(v) = all_css.get_mut(&key) {
+                    v.push(value);
+                } else {
+                    all_css.insert(key, vec![value]);
+                }
             }
+            Err(msg) => errors.push(StyleError {
+                style: class_name,
+                msg,
+            }),
         }
     }
 
@@ -37,7 +53,7 @@ pub fn generate_site_styles(site_styles: HashSet<String>) -> String {
         }
     }
 
-    if let Some(q_vec) = &CONFIG.responsive.query {
+    if let Some(q_vec) = &config.responsive.query {
         for q in q_vec {
             for (k, v) in &queries {
                 if &q.min_width == k {
@@ -47,29 +63,25 @@ pub fn generate_site_styles(site_styles: HashSet<String>) -> String {
         }
     }
 
-    style
+    (style, errors)
 }
 
-fn handle_css(class_name: &str) -> Option<(String, String)> {
+fn handle_css(class_name: &str, config: &Config) -> Result<(String, String), String> {
     let mut key = String::from("all");
 
-    let style = match parse_style(class_name) {
-        Some(s) => s,
-        None => return None,
-    };
+    let style = parse_style(class_name, config)?;
     if let Some(w) = style.responsive_width {
         key = w;
     }
     let c_name = handle_class_name(class_name, &style.pseudo_selectors);
 
-    let prop = match find_property(&style.property_alias) {
+    let prop = match find_property(&style.property_alias, config) {
         Some(p) => p,
         None => {
-            println!(
-                "\"{}\" style will no effect on site, because didn't find alias on css config",
-                &class_name
-            );
-            return None;
+            return Err(format!(
+                "will no effect on site, because didn't find \"{}\" alias on css config",
+                &style.property_alias
+            ));
         }
     };
     let prop_name = &prop.property_name;
@@ -78,10 +90,11 @@ fn handle_css(class_name: &str) -> Option<(String, String)> {
         &prop.keywords,
         &prop.keyword_aliases,
         &prop.data_types,
-    );
+        config,
+    )?;
     let value = format!("{} {{\n {}:{}\n}}\n", c_name, prop_name, prop_value);
 
-    Some((key, value))
+    Ok((key, value))
 }
 fn handle_class_name(c_name: &str, p_selectors: &Option<Vec<String>>) -> String {
     let mut class_name = c_name.to_string();
@@ -109,8 +122,8 @@ fn handle_class_name(c_name: &str, p_selectors: &Option<Vec<String>>) -> String
 
     class_name
 }
-fn find_property(p_name: &str) -> Option<&Property> {
-    if let Some(p) = CONFIG
+fn find_property<'a>(p_name: &str, config: &'a Config) -> Option<&'a Property> {
+    if let Some(p) = config
         .properties
         .property
         .iter()
@@ -118,7 +131,7 @@ fn find_property(p_name: &str) -> Option<&Property> {
     {
         return Some(p);
     }
-    if let Some(p) = CONFIG
+    if let Some(p) = config
         .properties
         .property
         .iter()
@@ -133,7 +146,8 @@ fn handle_prop_value(
     keywords: &Vec<String>,
     keyword_aliases: &Vec<String>,
     data_types: &Vec<String>,
-) -> String {
+    config: &Config,
+) -> Result<String, String> {
     let mut value = String::from(" ");
 
     for arg in args {
@@ -147,14 +161,14 @@ fn handle_prop_value(
                     }
                 }
                 "color" => {
-                    if let Some(v) = handle_color_value(arg) {
+                    if let Some(v) = handle_color_value(arg, config) {
                         value.push_str(&v);
                         value.push(' ');
                         break;
                     }
                 }
                 "length" => {
-                    if let Some(v) = handle_length_value(arg) {
+                    if let Some(v) = handle_length_value(arg, config) {
                         value.push_str(&v);
                         value.push(' ');
                         break;
@@ -181,10 +195,12 @@ fn handle_prop_value(
                         break;
                     }
                 }
-                _ => println!(
-                    "\nCSS GENERATOR :: Can't handle '{}' data type. possible mistyped.\n",
-                    &d_type
-                ),
+                _ => {
+                    return Err(format!(
+                        "can't be generated, because css config has unknown '{}' data type. possible mistyped.",
+                        &d_type
+                    ))
+                }
             }
         }
     }
@@ -192,7 +208,7 @@ fn handle_prop_value(
     value.pop();
     value.push(';');
 
-    value
+    Ok(value)
 }
 fn handle_keyword_value(
     arg: &String,
@@ -210,7 +226,7 @@ fn handle_keyword_value(
 
     None
 }
-fn handle_color_value(arg: &str) -> Option<String> {
+fn handle_color_value(arg: &str, config: &Config) -> Option<String> {
     let mut color: Option<String> = None;
 
     let p: Vec<&str> = arg.split("_").collect();
@@ -221,14 +237,14 @@ fn handle_color_value(arg: &str) -> Option<String> {
     }
 
     if l >= 1 {
-        if let Some(c) = CONFIG.colors.color.iter().find(|&x| x.color_alias == p[0]) {
+        if let Some(c) = config.colors.color.iter().find(|&x| x.color_alias == p[0]) {
             color = Some(c.color.to_string());
         } else {
             return None;
         }
     }
     if l >= 2 {
-        match CONFIG.colors.range {
+        match config.colors.range {
             Some(r) => {
                 if let Ok(i) = p[1].parse::<u64>() {
                     color = linear_interpolate_color(&color.unwrap(), r, i);
@@ -240,7 +256,7 @@ fn handle_color_value(arg: &str) -> Option<String> {
         };
     }
     if l >= 3 {
-        match CONFIG.colors.transparency_range {
+        match config.colors.transparency_range {
             Some(r) => {
                 if let Ok(i) = p[2].parse::<u64>() {
                     let mut w_norm = i as f32 / r as f32;
@@ -309,14 +325,14 @@ fn handle_color_value(arg: &str) -> Option<String> {
 
     Some(format!("#{}", color.unwrap()))
 }
-fn handle_length_value(arg: &str) -> Option<String> {
+fn handle_length_value(arg: &str, config: &Config) -> Option<String> {
     let value: Option<String> = None;
-    if let Some(s) = &CONFIG.lengths.default {
+    if let Some(s) = &config.lengths.default {
         if let Ok(_) = arg.parse::<f64>() {
             return Some(format!("{}{}", arg, s));
         }
     }
-    for unit in &CONFIG.lengths.units {
+    for unit in &config.lengths.units {
         if arg.ends_with(unit) {
             if let Some(t) = arg.get(..(arg.len() - unit.len())) {
                 if let Ok(_) = t.parse::<f64>() {
@@ -350,7 +366,7 @@ fn handle_string_value(arg: &str) -> Option<String> {
     None
 }
 
-fn parse_style(class_name: &str) -> Option<Style> {
+fn parse_style(class_name: &str, config: &Config) -> Result<Style, String> {
     let mut responsive_width: Option<String> = None;
     let mut pseudo_vec: Vec<String> = Vec::new();
     let mut pseudo_selectors: Option<Vec<String>> = None;
@@ -359,13 +375,10 @@ fn parse_style(class_name: &str) -> Option<Style> {
     let mut fp: Vec<&str> = class_name.split(":").collect();
     let rhs = match fp.pop() {
         Some(s) => s,
-        None => {
-            println!("Error while parsing style. Style: {}", class_name);
-            return None;
-        }
+        None => return Err("can't be parsed as a style".to_string()),
     };
     if fp.len() > 0 && fp[0] != "" {
-        if let Some(q_vec) = &CONFIG.responsive.query {
+        if let Some(q_vec) = &config.responsive.query {
             if let Some(qu) = q_vec.iter().find(|&q| q.name == fp[0]) {
                 fp.remove(0);
                 responsive_width = Some(qu.min_width.to_string());
@@ -384,19 +397,17 @@ fn parse_style(class_name: &str) -> Option<Style> {
     let property_alias = if sp[0] != "" {
         sp.remove(0).to_string()
     } else {
-        println!("Error while parsing style. Style: {}", class_name);
-        return None;
+        return Err("can't be parsed as a style, property alias is missing".to_string());
     };
     if sp.len() > 0 {
         for arg in sp {
             args.push(arg.to_string());
         }
     } else {
-        println!("Error while parsing style. Style: {}", class_name);
-        return None;
+        return Err("can't be parsed as a style, value is missing".to_string());
     }
 
-    Some(Style {
+    Ok(Style {
         property_alias,
         args,
         responsive_width,
@@ -1,2 +1,2 @@
-mod css_conf_parser;
+pub mod css_conf_parser;
 pub mod css_generator;
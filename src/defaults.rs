@@ -1,6 +1,18 @@
 lazy_static! {
     pub static ref CONF: &'static str = r#"
     default_template = "default_template.html"
+    date_format = "%Y-%m-%d"
+    highlight_theme = "InspiredGitHub"
+    # base_url = "https://example.com"
+    # title = "My Site"
+    # author = "Me"
+    # sitemap = true
+
+    # [feed]
+    # dirs = ["posts"]
+    # rss = true
+    # atom = false
+    # full_content = false
 "#;
     pub static ref INDEX: &'static str = r#"#+template: empty_template.html
 * #+style: bc-#15191A w-100% minh-100vh dis-flex fdir-column jc-center ai-center ta-center
@@ -28,6 +40,7 @@ MAGICAL STATIC SITE GENERATOR
     <meta name="viewport" content="width=device-width, initial-scale=1">
     <link rel="stylesheet" type="text/css" href="/theme.css">
     <link rel="stylesheet" type="text/css" href="/scroll_style.css">
+    <link rel="stylesheet" type="text/css" href="/highlight.css">
     {{ if title }}<title>{title}</title>{{ endif }}
   </head>
   <body>
@@ -40,6 +53,7 @@ MAGICAL STATIC SITE GENERATOR
       {{ if date }}<h1 class="date">{date}</h1>{{ endif }}
       {{ if title }}<hr style="margin-bottom: 10rem;">{{ endif }}
       <<page>>
+      <<footnotes>>
     </div>
     <footer>
       Built with ✨ by <a href="https://github.com/metinUr/scroll">scroll</a>
@@ -54,6 +68,7 @@ MAGICAL STATIC SITE GENERATOR
     <meta name="viewport" content="width=device-width, initial-scale=1">
     <link rel="stylesheet" type="text/css" href="/theme.css">
     <link rel="stylesheet" type="text/css" href="/scroll_style.css">
+    <link rel="stylesheet" type="text/css" href="/highlight.css">
     {{ if title }}<title>{title}</title>{{ endif }}
   </head>
   <body>
@@ -1570,5 +1585,31 @@ footer a {
   font-size: 2rem;
   color: black;
 }
+
+.center {
+  text-align: center;
+}
+
+.verse {
+  font-style: italic;
+}
+
+.todo {
+  color: #c0392b;
+}
+
+.done {
+  color: #27ae60;
+}
+
+.priority,
+.tag {
+  font-size: 0.6em;
+  opacity: 0.6;
+}
+
+.tag {
+  margin-left: 0.5rem;
+}
 "#;
 }
@@ -0,0 +1,109 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub enum ScrollError {
+    Config {
+        path: PathBuf,
+        line: Option<usize>,
+        msg: String,
+    },
+    Parse {
+        path: PathBuf,
+        line: Option<usize>,
+        msg: String,
+    },
+    Template {
+        path: PathBuf,
+        line: Option<usize>,
+        page: PathBuf,
+        msg: String,
+    },
+    Io {
+        path: PathBuf,
+        err: std::io::Error,
+    },
+    Css {
+        path: PathBuf,
+        style: String,
+        msg: String,
+    },
+}
+
+impl ScrollError {
+    pub fn io(path: &Path, err: std::io::Error) -> ScrollError {
+        ScrollError::Io {
+            path: path.to_path_buf(),
+            err,
+        }
+    }
+
+    pub fn from_toml(path: &Path, err: toml::de::Error) -> ScrollError {
+        ScrollError::Config {
+            path: path.to_path_buf(),
+            line: err.line_col().map(|(l, _)| l + 1),
+            msg: err.to_string(),
+        }
+    }
+
+    pub fn from_template(
+        template: &Path,
+        page: &Path,
+        err: tinytemplate::error::Error,
+    ) -> ScrollError {
+        use tinytemplate::error::Error::*;
+        let (line, msg) = match &err {
+            ParseError { msg, line, .. } | RenderError { msg, line, .. } => {
+                (Some(*line), msg.to_string())
+            }
+            CalledTemplateError { line, .. } | CalledFormatterError { line, .. } => {
+                (Some(*line), err.to_string())
+            }
+            _ => (None, err.to_string()),
+        };
+        ScrollError::Template {
+            path: template.to_path_buf(),
+            line,
+            page: page.to_path_buf(),
+            msg,
+        }
+    }
+}
+
+impl fmt::Display for ScrollError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn location(path: &Path, line: &Option<usize>) -> String {
+            match line {
+                Some(l) => format!("{}:{}", path.display(), l),
+                None => path.display().to_string(),
+            }
+        }
+
+        match self {
+            ScrollError::Config { path, line, msg } => {
+                write!(f, "{}: config error: {}", location(path, line), msg)
+            }
+            ScrollError::Parse { path, line, msg } => {
+                write!(f, "{}: parse error: {}", location(path, line), msg)
+            }
+            ScrollError::Template {
+                path,
+                line,
+                page,
+                msg,
+            } => write!(
+                f,
+                "{}: template error while rendering {}: {}",
+                location(path, line),
+                page.display(),
+                msg
+            ),
+            ScrollError::Io { path, err } => write!(f, "{}: io error: {}", path.display(), err),
+            ScrollError::Css { path, style, msg } => {
+                write!(f, "{}: css error: \"{}\" {}", path.display(), style, msg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScrollError {}
@@ -0,0 +1,118 @@
+use chrono::{NaiveDateTime, Utc};
+
+use crate::config::ScrollConfig;
+
+pub struct FeedEntry<'a> {
+    pub title: &'a str,
+    pub url: String,
+    pub date: NaiveDateTime,
+    pub summary: &'a str,
+    pub content: Option<String>,
+}
+
+pub fn generate_rss(config: &ScrollConfig, link: &str, entries: &[FeedEntry]) -> String {
+    let title = config.title.as_deref().unwrap_or_default();
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" \
+         xmlns:content=\"http://purl.org/rss/1.0/modules/content/\" \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<channel>\n",
+    );
+    xml.push_str(&format!("<title>{}</title>\n", escape(title)));
+    xml.push_str(&format!("<link>{}</link>\n", escape(link)));
+    xml.push_str(&format!(
+        "<atom:link href=\"{}feed.xml\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        escape(link)
+    ));
+    xml.push_str(&format!("<description>{}</description>\n", escape(title)));
+    let updated = entries
+        .first()
+        .map_or_else(|| Utc::now().naive_utc(), |e| e.date);
+    xml.push_str(&format!(
+        "<lastBuildDate>{}</lastBuildDate>\n",
+        updated.format("%a, %d %b %Y %H:%M:%S +0000")
+    ));
+
+    for entry in entries {
+        xml.push_str("<item>\n");
+        xml.push_str(&format!("<title>{}</title>\n", escape(entry.title)));
+        xml.push_str(&format!("<link>{}</link>\n", escape(&entry.url)));
+        xml.push_str(&format!("<guid>{}</guid>\n", escape(&entry.url)));
+        xml.push_str(&format!(
+            "<pubDate>{}</pubDate>\n",
+            entry.date.format("%a, %d %b %Y %H:%M:%S +0000")
+        ));
+        if let Some(author) = &config.author {
+            xml.push_str(&format!("<dc:creator>{}</dc:creator>\n", escape(author)));
+        }
+        xml.push_str(&format!(
+            "<description>{}</description>\n",
+            escape(entry.summary)
+        ));
+        if let Some(content) = &entry.content {
+            xml.push_str(&format!(
+                "<content:encoded><![CDATA[{}]]></content:encoded>\n",
+                content.replace("]]>", "]]]]><![CDATA[>")
+            ));
+        }
+        xml.push_str("</item>\n");
+    }
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+pub fn generate_atom(config: &ScrollConfig, link: &str, entries: &[FeedEntry]) -> String {
+    let title = config.title.as_deref().unwrap_or_default();
+    let author = config.author.as_deref().unwrap_or(title);
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+    );
+    xml.push_str(&format!("<title>{}</title>\n", escape(title)));
+    xml.push_str(&format!("<id>{}</id>\n", escape(link)));
+    xml.push_str(&format!("<link href=\"{}\"/>\n", escape(link)));
+    xml.push_str(&format!(
+        "<link href=\"{}atom.xml\" rel=\"self\"/>\n",
+        escape(link)
+    ));
+    let updated = entries
+        .first()
+        .map_or_else(|| Utc::now().naive_utc(), |e| e.date);
+    xml.push_str(&format!(
+        "<updated>{}</updated>\n",
+        updated.format("%Y-%m-%dT%H:%M:%SZ")
+    ));
+    xml.push_str(&format!(
+        "<author><name>{}</name></author>\n",
+        escape(author)
+    ));
+
+    for entry in entries {
+        xml.push_str("<entry>\n");
+        xml.push_str(&format!("<title>{}</title>\n", escape(entry.title)));
+        xml.push_str(&format!("<id>{}</id>\n", escape(&entry.url)));
+        xml.push_str(&format!("<link href=\"{}\"/>\n", escape(&entry.url)));
+        xml.push_str(&format!(
+            "<updated>{}</updated>\n",
+            entry.date.format("%Y-%m-%dT%H:%M:%SZ")
+        ));
+        xml.push_str(&format!("<summary>{}</summary>\n", escape(entry.summary)));
+        if let Some(content) = &entry.content {
+            xml.push_str(&format!(
+                "<content type=\"html\">{}</content>\n",
+                escape(content)
+            ));
+        }
+        xml.push_str("</entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
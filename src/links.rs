@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::sop::ast_gen::Anchor;
+use crate::sop::include;
+
+// What a link can point to in another page of the site.
+pub struct LinkedPage<'a> {
+    pub url: &'a str,
+    pub anchors: &'a [Anchor],
+}
+
+// Where a link found in `page` points to, once every page of the site is
+// parsed: `[[*Heading]]`, `[[Heading]]`, `[[#custom-id]]`, `[[id:...]]`,
+// `[[file:guide.org::*Heading]]` or `guide.md#heading`. `None` leaves the
+// link as it is, an error tells why the target can't be found.
+pub fn resolve(
+    target: &str,
+    page: &Path,
+    pages: &HashMap<&Path, LinkedPage>,
+) -> Result<Option<String>, String> {
+    if let Some(org_id) = target.strip_prefix("id:") {
+        let from = pages.get(page).map_or("/", |p| p.url);
+        return pages
+            .values()
+            .find_map(|p| {
+                p.anchors
+                    .iter()
+                    .find(|a| a.org_id.as_deref() == Some(org_id))
+                    .map(|a| format!("{}#{}", relative_url(from, p.url), a.id))
+            })
+            .map(Some)
+            .ok_or_else(|| format!("no headline has the id \"{}\"", org_id));
+    }
+    if target.starts_with('*') || target.starts_with('#') {
+        let anchors = pages.get(page).map_or(&[][..], |p| p.anchors);
+        return find_anchor(target, anchors)
+            .map(|id| Some(format!("#{}", id)))
+            .ok_or_else(|| format!("no headline matches \"{}\"", target));
+    }
+    if target.contains("://") || target.starts_with("mailto:") {
+        return Ok(None);
+    }
+
+    let link = target.strip_prefix("file:").unwrap_or(target);
+    let (file, search) = match link.split_once("::") {
+        Some((f, s)) => (f, Some(s)),
+        None => match link.find('#') {
+            Some(i) => (&link[..i], Some(&link[i..])),
+            None => (link, None),
+        },
+    };
+    let html = match file
+        .strip_suffix(".org")
+        .or_else(|| file.strip_suffix(".md"))
+    {
+        Some(f) => format!("{}.html", f),
+        None if link != target => return Ok(Some(link.to_string())),
+        None if is_search(target, page) => {
+            let anchors = pages.get(page).map_or(&[][..], |p| p.anchors);
+            return find_anchor(target, anchors)
+                .map(|id| Some(format!("#{}", id)))
+                .ok_or_else(|| format!("no headline matches \"{}\"", target));
+        }
+        None => return Ok(None),
+    };
+
+    let path = include::resolve_path(page, file);
+    let linked = pages
+        .get(path.as_path())
+        .ok_or_else(|| format!("no page {}", path.display()))?;
+    match search {
+        Some(s) => find_anchor(s, linked.anchors)
+            .map(|id| Some(format!("{}#{}", html, id)))
+            .ok_or_else(|| format!("no headline matches \"{}\" in {}", s, path.display())),
+        None => Ok(Some(html)),
+    }
+}
+
+// `to` as a link from the page at `from`, both urls from the site's root,
+// like links between files are written.
+fn relative_url(from: &str, to: &str) -> String {
+    if from == to {
+        return String::new();
+    }
+    let from_dirs: Vec<&str> = from.trim_start_matches('/').split('/').collect();
+    let from_dirs = &from_dirs[..from_dirs.len() - 1];
+    let to_parts: Vec<&str> = to.trim_start_matches('/').split('/').collect();
+    let common = from_dirs
+        .iter()
+        .zip(&to_parts[..to_parts.len() - 1])
+        .take_while(|(a, b)| a == b)
+        .count();
+    format!(
+        "{}{}",
+        "../".repeat(from_dirs.len() - common),
+        to_parts[common..].join("/")
+    )
+}
+
+// Org takes `[[Some title]]` for a headline of the page, while `logo.png`
+// or `./about` are files and markdown targets are urls.
+fn is_search(target: &str, page: &Path) -> bool {
+    page.extension().is_some_and(|e| e == "org")
+        && !target.contains('/')
+        && (target.contains(char::is_whitespace) || Path::new(target).extension().is_none())
+}
+
+// `*Title` and plain text match a headline title, `#id` its `:CUSTOM_ID:`
+// or its id in the html.
+fn find_anchor<'a>(search: &str, anchors: &'a [Anchor]) -> Option<&'a str> {
+    let anchor = match search.strip_prefix('#') {
+        Some(id) => anchors
+            .iter()
+            .find(|a| a.custom_id.as_deref() == Some(id))
+            .or_else(|| anchors.iter().find(|a| a.id == id)),
+        None => {
+            let title = normalize(search.strip_prefix('*').unwrap_or(search));
+            anchors
+                .iter()
+                .find(|a| normalize(&a.title).eq_ignore_ascii_case(&title))
+        }
+    };
+    anchor.map(|a| a.id.as_str())
+}
+
+fn normalize(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
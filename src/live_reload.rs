@@ -0,0 +1,95 @@
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+pub const RELOAD_PATH: &str = "/__scroll/reload";
+
+// Injected into every served html page by `scroll serve`, never written to
+// `public`. It long-polls `RELOAD_PATH` and reloads the page, or only swaps
+// stylesheets when the rebuild touched nothing but css.
+const RELOAD_SCRIPT: &str = r#"<script>
+(function () {
+  var version = null;
+  function swapStyles() {
+    var links = document.querySelectorAll('link[rel="stylesheet"]');
+    for (var i = 0; i < links.length; i++) {
+      var href = links[i].getAttribute("href").split("?")[0];
+      links[i].setAttribute("href", href + "?v=" + Date.now());
+    }
+  }
+  function poll() {
+    fetch("/__scroll/reload" + (version === null ? "" : "?v=" + version))
+      .then(function (r) { return r.json(); })
+      .then(function (r) {
+        if (version !== null && r.version !== version) {
+          if (!r.css_only) {
+            location.reload();
+            return;
+          }
+          swapStyles();
+        }
+        version = r.version;
+        poll();
+      })
+      .catch(function () { setTimeout(poll, 1000); });
+  }
+  poll();
+})();
+</script>
+"#;
+
+#[derive(Debug, Default)]
+struct ReloadState {
+    version: u64,
+    // version of the last rebuild that touched more than css.
+    last_full: u64,
+}
+
+#[derive(Debug, Default)]
+pub struct LiveReload {
+    state: Mutex<ReloadState>,
+    changed: Condvar,
+}
+
+impl LiveReload {
+    pub fn new() -> LiveReload {
+        LiveReload::default()
+    }
+
+    pub fn notify(&self, css_only: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.version += 1;
+        if !css_only {
+            state.last_full = state.version;
+        }
+        self.changed.notify_all();
+    }
+
+    // Blocks until a rebuild newer than `since` happens, or the timeout passes,
+    // and answers with the json the reload script expects. Styles are only
+    // swapped when every rebuild since `since` was css only.
+    pub fn wait(&self, since: Option<u64>) -> String {
+        let mut state = self.state.lock().unwrap();
+        if let Some(v) = since {
+            state = self
+                .changed
+                .wait_timeout_while(state, Duration::from_secs(25), |s| s.version == v)
+                .unwrap()
+                .0;
+        }
+        let css_only = match since {
+            Some(v) => state.version > v && state.last_full <= v,
+            None => state.last_full < state.version,
+        };
+        format!(
+            "{{\"version\": {}, \"css_only\": {}}}",
+            state.version, css_only
+        )
+    }
+
+    pub fn inject_script(html: &str) -> String {
+        match html.rfind("</body>") {
+            Some(i) => format!("{}{}{}", &html[..i], RELOAD_SCRIPT, &html[i..]),
+            None => format!("{}{}", html, RELOAD_SCRIPT),
+        }
+    }
+}
@@ -3,8 +3,15 @@ extern crate lazy_static;
 
 mod app;
 mod commands;
+mod config;
 mod css_gen;
 pub mod defaults;
+mod error;
+mod feed;
+mod links;
+mod live_reload;
+mod site;
+mod sitemap;
 mod sop;
 
 fn main() {
@@ -0,0 +1,905 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use std::collections::btree_map::BTreeMap;
+use std::collections::hash_map::HashMap;
+use std::collections::hash_set::HashSet;
+use std::path::{Path, PathBuf};
+
+use std::{fs, fs::File};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::config::ScrollConfig;
+use crate::css_gen::css_conf_parser::{self, Config as CssConfig};
+use crate::css_gen::css_generator;
+use crate::defaults;
+use crate::error::ScrollError;
+use crate::feed::{self, FeedEntry};
+use crate::links::{self, LinkedPage};
+use crate::sitemap::{self, SitemapEntry};
+use crate::sop;
+use sop::ast_gen::{self, Anchor, Footnote, LinkType, OrgDoc, OrgElement, TocEntry};
+use sop::highlight;
+use sop::md_parser::MdParser;
+use sop::parser::OrgParser;
+use std::io::prelude::*;
+use tinytemplate::TinyTemplate;
+
+lazy_static! {
+    static ref BLACK_LIST: Vec<&'static str> =
+        vec!["templates", "style_config.toml", "public", "scroll.toml"];
+}
+
+// Page metadata exposed to templates, both for the page being rendered and
+// for every page of the site through `site.pages`. `date` is the raw
+// `#+date`, `formatted_date` follows `date_format` of scroll.toml.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+struct PageMeta {
+    title: String,
+    summary: String,
+    date: String,
+    formatted_date: String,
+    #[serde(skip)]
+    parsed_date: Option<NaiveDateTime>,
+    url: String,
+    tags: Vec<String>,
+    section: String,
+}
+
+// `pages` is sorted newest first, `sections` has the same pages grouped by
+// every directory they are under, e.g. `site.sections.posts`.
+#[derive(Debug, Default, Serialize)]
+struct SiteContext {
+    pages: Vec<PageMeta>,
+    sections: BTreeMap<String, Vec<PageMeta>>,
+}
+
+#[derive(Serialize)]
+struct Page<'a> {
+    #[serde(flatten)]
+    meta: &'a PageMeta,
+    toc: &'a [TocEntry],
+    site: &'a SiteContext,
+}
+
+// What a rendered page depends on and what other pages know about it, kept so
+// watch mode can find the pages affected by a change instead of rebuilding
+// the whole site.
+#[derive(Debug)]
+struct SitePage {
+    template: Option<String>,
+    styles: HashSet<String>,
+    meta: PageMeta,
+    // kept to render the page once its links are resolved.
+    ast: OrgElement,
+    footnotes: Vec<Footnote>,
+    toc: Vec<TocEntry>,
+    anchors: Vec<Anchor>,
+    includes: Vec<PathBuf>,
+    sitemap: bool,
+}
+
+// What an update wrote into `public`, so the dev server knows whether a
+// stylesheet swap is enough or the page has to be reloaded.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Change {
+    #[default]
+    Nothing,
+    Css,
+    Pages,
+}
+
+#[derive(Debug, Default)]
+pub struct Site {
+    config: ScrollConfig,
+    css_config: CssConfig,
+    default_template: String,
+    pages: HashMap<PathBuf, SitePage>,
+    context: SiteContext,
+    styles: HashSet<String>,
+    errors: Vec<ScrollError>,
+    warnings: Vec<ScrollError>,
+    change: Change,
+}
+
+impl Site {
+    pub fn new() -> Site {
+        Site::default()
+    }
+
+    // Problems that don't fail the build, collected since the last call.
+    pub fn take_warnings(&mut self) -> Vec<ScrollError> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn build(&mut self) -> Vec<ScrollError> {
+        let _ = fs::remove_dir_all("./public");
+
+        self.load_config();
+        self.load_css_config();
+        self.pages.clear();
+
+        // every page is parsed before rendering any of them, so templates
+        // can list the whole site.
+        let mut pages = Vec::new();
+        for path in self.walk(Path::new(".")) {
+            if is_page(&path) {
+                if self.load_page(&path) {
+                    pages.push(path);
+                }
+            } else {
+                self.copy_file(&path);
+            }
+        }
+        self.refresh_context();
+        for path in pages {
+            self.write_page(&path);
+        }
+        self.generate_styles(true);
+        self.generate_highlight_css();
+        self.generate_feeds();
+        self.generate_sitemap();
+
+        std::mem::take(&mut self.errors)
+    }
+
+    // Parses every page to learn its dependencies without writing anything,
+    // used when watching a site that was already built.
+    pub fn scan(&mut self) -> Vec<ScrollError> {
+        self.load_config();
+        self.load_css_config();
+        self.pages.clear();
+
+        for path in self.walk(Path::new(".")) {
+            if is_page(&path) {
+                self.load_page(&path);
+            }
+        }
+        self.refresh_context();
+        // feeds render the pages left unchanged from their links.
+        let paths: Vec<PathBuf> = self.pages.keys().cloned().collect();
+        for path in paths {
+            self.resolve_links(&path);
+        }
+        self.styles = self.site_styles().keys().cloned().collect();
+
+        std::mem::take(&mut self.errors)
+    }
+
+    pub fn update(&mut self, path: &Path) -> (Change, Vec<ScrollError>) {
+        self.change = Change::Nothing;
+        let path = match site_relative_path(path) {
+            Some(p) => p,
+            None => return (Change::Nothing, Vec::new()),
+        };
+
+        if path == Path::new("./scroll.toml") {
+            self.load_config();
+            self.render_pages(|_, _| true);
+            self.generate_highlight_css();
+            self.generate_feeds();
+            self.generate_sitemap();
+        } else if path == Path::new("./style_config.toml") {
+            self.load_css_config();
+            self.generate_styles(true);
+        } else if let Ok(t) = path.strip_prefix("./templates") {
+            let name = t.to_string_lossy().to_string();
+            if name == self.config.default_template {
+                self.load_default_template();
+                self.render_pages(|_, p| p.template.is_none() || p.template == Some(name.clone()));
+            } else {
+                self.render_pages(|_, p| p.template == Some(name.clone()));
+            }
+        } else if is_ignored(&path) {
+            return (Change::Nothing, Vec::new());
+        } else if is_unpublished(&path) {
+            // only the pages including it below are rebuilt.
+        } else if path.is_dir() {
+            // a directory moved into the site, built like the whole site is.
+            for p in self.walk(&path) {
+                if is_page(&p) {
+                    self.load_page(&p);
+                } else {
+                    self.copy_file(&p);
+                }
+            }
+            self.render_pages(|_, _| true);
+            self.generate_feeds();
+            self.generate_sitemap();
+        } else if is_page(&path) {
+            let old = self
+                .pages
+                .get(&path)
+                .map(|p| (p.meta.clone(), p.anchors.clone()));
+            if self.load_page(&path) {
+                // a new title, date etc. can show up in any page listing,
+                // new headlines can fix or break links from other pages.
+                let page = &self.pages[&path];
+                if old.as_ref() != Some(&(page.meta.clone(), page.anchors.clone())) {
+                    self.render_pages(|p, _| p != path);
+                }
+                self.write_page(&path);
+            } else {
+                self.refresh_context();
+            }
+            self.generate_styles(false);
+            self.generate_feeds();
+            self.generate_sitemap();
+        } else {
+            self.copy_file(&path);
+        }
+        // pages including the file show its new content.
+        if self.pages.values().any(|p| p.includes.contains(&path)) {
+            self.render_pages(|_, p| p.includes.contains(&path));
+            self.generate_feeds();
+        }
+
+        (self.change, std::mem::take(&mut self.errors))
+    }
+
+    // Deletes what a removed source produced in `public`.
+    pub fn remove(&mut self, path: &Path) -> (Change, Vec<ScrollError>) {
+        self.change = Change::Nothing;
+        let path = match site_relative_path(path) {
+            Some(p) => p,
+            None => return (Change::Nothing, Vec::new()),
+        };
+
+        // configs and templates fall back to their defaults, with an error.
+        if path == Path::new("./scroll.toml")
+            || path == Path::new("./style_config.toml")
+            || path.starts_with("./templates")
+        {
+            return self.update(&path);
+        }
+        if is_ignored(&path) {
+            return (Change::Nothing, Vec::new());
+        }
+
+        let removed_pages: Vec<PathBuf> = self
+            .pages
+            .keys()
+            .filter(|p| p.starts_with(&path))
+            .cloned()
+            .collect();
+        for p in &removed_pages {
+            self.pages.remove(p);
+            self.remove_output(p, true);
+        }
+        if !is_page(&path) {
+            self.remove_output(&path, false);
+        }
+        if !removed_pages.is_empty() {
+            self.render_pages(|_, _| true);
+            self.generate_feeds();
+            self.generate_sitemap();
+        } else {
+            self.render_pages(|_, p| p.includes.iter().any(|i| i.starts_with(&path)));
+        }
+        self.generate_styles(false);
+
+        (self.change, std::mem::take(&mut self.errors))
+    }
+
+    fn remove_output(&mut self, path: &Path, is_page: bool) {
+        let out = match site_path(path, is_page) {
+            Some(p) => PathBuf::from(p),
+            None => return,
+        };
+        let result = if out.is_dir() {
+            fs::remove_dir_all(&out)
+        } else if out.is_file() {
+            fs::remove_file(&out)
+        } else {
+            return;
+        };
+        match result {
+            Ok(_) => self.mark_change(Change::Pages),
+            Err(e) => self.errors.push(ScrollError::io(&out, e)),
+        }
+    }
+
+    fn mark_change(&mut self, change: Change) {
+        if change > self.change {
+            self.change = change;
+        }
+    }
+
+    fn load_config(&mut self) {
+        self.config = ScrollConfig::load().unwrap_or_else(|e| {
+            self.errors.push(e);
+            ScrollConfig::default()
+        });
+        self.load_default_template();
+    }
+
+    fn load_default_template(&mut self) {
+        let path = self.default_template_path();
+        self.default_template = fs::read_to_string(&path).unwrap_or_else(|e| {
+            self.errors.push(ScrollError::io(&path, e));
+            defaults::TEMPLATE.to_string()
+        });
+    }
+
+    fn default_template_path(&self) -> PathBuf {
+        PathBuf::from(format!("templates/{}", self.config.default_template))
+    }
+
+    fn load_css_config(&mut self) {
+        self.css_config = css_conf_parser::load_config().unwrap_or_else(|e| {
+            self.errors.push(e);
+            CssConfig::default()
+        });
+    }
+
+    fn walk(&mut self, root: &Path) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        for entry in WalkDir::new(root).into_iter().filter_entry(|e| !is_bl(e)) {
+            match entry {
+                Ok(e) => paths.push(e.into_path()),
+                Err(err) => {
+                    let path = err.path().unwrap_or(Path::new(".")).to_path_buf();
+                    self.errors.push(ScrollError::io(&path, err.into()));
+                }
+            }
+        }
+        paths
+    }
+
+    fn render_pages<F: Fn(&Path, &SitePage) -> bool>(&mut self, filter: F) {
+        let paths: Vec<PathBuf> = self
+            .pages
+            .iter()
+            .filter(|(p, sp)| filter(p, sp))
+            .map(|(p, _)| p.to_path_buf())
+            .collect();
+        let paths: Vec<PathBuf> = paths.into_iter().filter(|p| self.load_page(p)).collect();
+        self.refresh_context();
+        for path in paths {
+            self.write_page(&path);
+        }
+        self.generate_styles(false);
+    }
+
+    fn refresh_context(&mut self) {
+        let mut pages: Vec<PageMeta> = self.pages.values().map(|p| p.meta.clone()).collect();
+        pages.sort_by(|a, b| {
+            b.parsed_date
+                .cmp(&a.parsed_date)
+                .then_with(|| a.title.cmp(&b.title))
+        });
+
+        let mut sections: BTreeMap<String, Vec<PageMeta>> = BTreeMap::new();
+        for page in &pages {
+            let mut section = String::new();
+            for dir in page.section.split('/').filter(|d| !d.is_empty()) {
+                if !section.is_empty() {
+                    section.push('/');
+                }
+                section.push_str(dir);
+                sections
+                    .entry(section.clone())
+                    .or_default()
+                    .push(page.clone());
+            }
+        }
+
+        self.context = SiteContext { pages, sections };
+    }
+
+    // every style remembers a page it is used in, so css errors can point to
+    // a file.
+    fn site_styles(&self) -> HashMap<String, PathBuf> {
+        let mut site_styles = HashMap::new();
+        for (path, page) in &self.pages {
+            for style in &page.styles {
+                site_styles
+                    .entry(style.to_string())
+                    .or_insert_with(|| path.to_path_buf());
+            }
+        }
+        site_styles
+    }
+
+    // Regenerates `scroll_style.css` only when the union of page styles
+    // changed since the last time it was written, unless `force` is set.
+    fn generate_styles(&mut self, force: bool) {
+        let site_styles = self.site_styles();
+        let styles: HashSet<String> = site_styles.keys().cloned().collect();
+        if !force && styles == self.styles {
+            return;
+        }
+
+        let (site_css, style_errors) =
+            css_generator::generate_site_styles(styles.clone(), &self.css_config);
+        for e in style_errors {
+            self.errors.push(ScrollError::Css {
+                path: site_styles[&e.style].clone(),
+                style: e.style,
+                msg: e.msg,
+            });
+        }
+        let css_path = Path::new("./public/scroll_style.css");
+        if let Err(e) = File::create(css_path).and_then(|mut f| f.write_all(site_css.as_bytes())) {
+            self.errors.push(ScrollError::io(css_path, e));
+        }
+        self.styles = styles;
+        self.mark_change(Change::Css);
+    }
+
+    fn generate_highlight_css(&mut self) {
+        let css = match highlight::theme_css(&self.config.highlight_theme) {
+            Ok(css) => css,
+            Err(msg) => {
+                self.errors.push(ScrollError::Config {
+                    path: PathBuf::from("scroll.toml"),
+                    line: None,
+                    msg,
+                });
+                return;
+            }
+        };
+        let css_path = Path::new("./public/highlight.css");
+        if let Err(e) = File::create(css_path).and_then(|mut f| f.write_all(css.as_bytes())) {
+            self.errors.push(ScrollError::io(css_path, e));
+        }
+        self.mark_change(Change::Css);
+    }
+
+    fn base_url(&mut self, needed_for: &str) -> Option<String> {
+        match &self.config.base_url {
+            Some(u) => Some(u.trim_end_matches('/').to_string()),
+            None => {
+                self.errors.push(ScrollError::Config {
+                    path: PathBuf::from("scroll.toml"),
+                    line: None,
+                    msg: format!("base_url is required to generate {}.", needed_for),
+                });
+                None
+            }
+        }
+    }
+
+    // Lists every rendered page but the ones with `#+sitemap: no`, a
+    // robots.txt of the site itself is copied instead of generated.
+    fn generate_sitemap(&mut self) {
+        if !self.config.sitemap {
+            return;
+        }
+        let base_url = match self.base_url("a sitemap") {
+            Some(u) => u,
+            None => return,
+        };
+
+        let mut entries: Vec<SitemapEntry> = self
+            .pages
+            .iter()
+            .filter(|(_, p)| p.sitemap)
+            .map(|(path, p)| SitemapEntry {
+                url: format!("{}{}", base_url, p.meta.url),
+                lastmod: page_date(path, p).map(|d| d.format("%Y-%m-%d").to_string()),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.url.cmp(&b.url));
+
+        let mut files = vec![(
+            PathBuf::from("./public/sitemap.xml"),
+            sitemap::generate_sitemap(&entries),
+        )];
+        if !Path::new("./robots.txt").exists() {
+            files.push((
+                PathBuf::from("./public/robots.txt"),
+                sitemap::generate_robots(&format!("{}/sitemap.xml", base_url)),
+            ));
+        }
+        for (path, content) in files {
+            if let Err(e) = fs::create_dir_all("./public")
+                .and_then(|_| File::create(&path))
+                .and_then(|mut f| f.write_all(content.as_bytes()))
+            {
+                self.errors.push(ScrollError::io(&path, e));
+            }
+        }
+    }
+
+    fn generate_feeds(&mut self) {
+        if self.config.feed.is_none() {
+            return;
+        }
+        let base_url = match self.base_url("feeds") {
+            Some(u) => u,
+            None => return,
+        };
+        let base_url = base_url.as_str();
+        let feed_config = self.config.feed.as_ref().unwrap();
+
+        let mut feed_errors = Vec::new();
+        for dir in &feed_config.dirs {
+            let dir = dir.trim_matches('/').trim_start_matches("./");
+            let mut entries: Vec<FeedEntry> = self
+                .pages
+                .iter()
+                .filter(|(_, p)| {
+                    dir.is_empty()
+                        || p.meta.section == dir
+                        || p.meta.section.starts_with(&format!("{}/", dir))
+                })
+                .filter_map(|(path, p)| {
+                    Some(FeedEntry {
+                        title: &p.meta.title,
+                        url: format!("{}{}", base_url, p.meta.url),
+                        date: page_date(path, p)?,
+                        summary: &p.meta.summary,
+                        content: if feed_config.full_content {
+                            Some(format!(
+                                "{}{}",
+                                OrgParser::generate_html(&p.ast),
+                                OrgParser::generate_footnotes(&p.footnotes)
+                            ))
+                        } else {
+                            None
+                        },
+                    })
+                })
+                .collect();
+            entries.sort_by_key(|e| std::cmp::Reverse(e.date));
+            if let Some(l) = feed_config.limit {
+                entries.truncate(l);
+            }
+
+            let out_dir = if dir.is_empty() {
+                PathBuf::from("./public")
+            } else {
+                Path::new("./public").join(dir)
+            };
+            let dir_url = if dir.is_empty() {
+                format!("{}/", base_url)
+            } else {
+                format!("{}/{}/", base_url, dir)
+            };
+
+            let mut feeds = Vec::new();
+            if feed_config.rss {
+                feeds.push((
+                    out_dir.join("feed.xml"),
+                    feed::generate_rss(&self.config, &dir_url, &entries),
+                ));
+            }
+            if feed_config.atom {
+                feeds.push((
+                    out_dir.join("atom.xml"),
+                    feed::generate_atom(&self.config, &dir_url, &entries),
+                ));
+            }
+            for (path, xml) in feeds {
+                if let Err(e) = fs::create_dir_all(&out_dir)
+                    .and_then(|_| File::create(&path))
+                    .and_then(|mut f| f.write_all(xml.as_bytes()))
+                {
+                    feed_errors.push(ScrollError::io(&path, e));
+                }
+            }
+        }
+        self.errors.append(&mut feed_errors);
+    }
+
+    fn copy_file(&mut self, path: &Path) {
+        let result = handle_site_path(path, false).and_then(|p| match p {
+            Some(p) if !path.is_dir() => fs::copy(path, &p)
+                .map(|_| ())
+                .map_err(|e| ScrollError::io(path, e)),
+            _ => Ok(()),
+        });
+        match result {
+            Ok(_) if path.extension().is_some_and(|e| e == "css") => self.mark_change(Change::Css),
+            Ok(_) => self.mark_change(Change::Pages),
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    fn parse_page(&mut self, path: &Path) -> Option<OrgDoc> {
+        let ast = if path.extension().is_some_and(|e| e == "md") {
+            MdParser::create_from_path(path).map(|p| p.create_ast())
+        } else {
+            OrgParser::create_from_path(path).map(|p| p.create_ast())
+        };
+        let ast = match ast {
+            Ok(a) => a,
+            Err(e) => {
+                self.errors.push(ScrollError::io(path, e));
+                return None;
+            }
+        };
+
+        for e in &ast.errors {
+            self.errors.push(ScrollError::Parse {
+                path: path.to_path_buf(),
+                line: e.line,
+                msg: e.msg.to_string(),
+            });
+        }
+        Some(ast)
+    }
+
+    // Parses a page and refreshes what the site knows about it.
+    fn load_page(&mut self, path: &Path) -> bool {
+        match self.parse_page(path) {
+            Some(ast) => {
+                if ast.parsed_date.is_none() && !ast.date.is_empty() {
+                    self.warnings.push(ScrollError::Parse {
+                        path: path.to_path_buf(),
+                        line: None,
+                        msg: format!("can't parse date \"{}\"", ast.date),
+                    });
+                }
+                let page = SitePage::new(path, ast, &self.config.date_format);
+                self.pages.insert(path.to_path_buf(), page);
+                true
+            }
+            None => {
+                self.pages.remove(path);
+                false
+            }
+        }
+    }
+
+    fn write_page(&mut self, path: &Path) {
+        self.resolve_links(path);
+        match self.create_html(path) {
+            Ok(_) => self.mark_change(Change::Pages),
+            Err(e) => self.errors.push(e),
+        }
+    }
+
+    // Points the links of a page at the pages and headlines they target, now
+    // that the whole site is known, warning about those that go nowhere.
+    fn resolve_links(&mut self, path: &Path) {
+        let mut page = match self.pages.remove(path) {
+            Some(p) => p,
+            None => return,
+        };
+        let (url, anchors) = (page.meta.url.clone(), page.anchors.clone());
+        let mut pages: HashMap<&Path, LinkedPage> = self
+            .pages
+            .iter()
+            .map(|(p, sp)| {
+                let linked = LinkedPage {
+                    url: &sp.meta.url,
+                    anchors: &sp.anchors,
+                };
+                (p.as_path(), linked)
+            })
+            .collect();
+        pages.insert(
+            path,
+            LinkedPage {
+                url: &url,
+                anchors: &anchors,
+            },
+        );
+
+        let mut warnings = Vec::new();
+        let mut resolve = |el: &mut OrgElement| {
+            if let OrgElement::Link {
+                link_type: LinkType::A,
+                link,
+                target,
+                ..
+            } = el
+            {
+                match links::resolve(target, path, &pages) {
+                    Ok(Some(href)) => *link = href,
+                    Ok(None) => (),
+                    Err(msg) => warnings.push(ScrollError::Parse {
+                        path: path.to_path_buf(),
+                        line: None,
+                        msg: format!("broken link \"{}\": {}", target, msg),
+                    }),
+                }
+            }
+        };
+        ast_gen::walk_mut(&mut page.ast, &mut resolve);
+        for footnote in &mut page.footnotes {
+            for el in &mut footnote.childs {
+                ast_gen::walk_mut(el, &mut resolve);
+            }
+        }
+
+        self.warnings.append(&mut warnings);
+        self.pages.insert(path.to_path_buf(), page);
+    }
+
+    fn create_html(&self, path: &Path) -> Result<(), ScrollError> {
+        let site_page = &self.pages[path];
+
+        let (template_path, page_template) = match &site_page.template {
+            Some(t) => {
+                let template_path = PathBuf::from(format!("templates/{}", t));
+                let page_template =
+                    fs::read_to_string(&template_path).map_err(|e| ScrollError::Template {
+                        path: template_path.clone(),
+                        line: None,
+                        page: path.to_path_buf(),
+                        msg: format!("Error while reading this template. Error: {}", e),
+                    })?;
+                (template_path, page_template)
+            }
+            None => (
+                self.default_template_path(),
+                self.default_template.to_string(),
+            ),
+        };
+
+        let mut tt = TinyTemplate::new();
+        tt.add_template("tmp", &page_template)
+            .map_err(|e| ScrollError::from_template(&template_path, path, e))?;
+
+        let page = Page {
+            meta: &site_page.meta,
+            toc: &site_page.toc,
+            site: &self.context,
+        };
+
+        let html = OrgParser::generate_html(&site_page.ast);
+        let footnotes = OrgParser::generate_footnotes(&site_page.footnotes);
+        // footnotes follow the page unless the template places them.
+        let rendered = tt
+            .render("tmp", &page)
+            .map_err(|e| ScrollError::from_template(&template_path, path, e))?
+            .replace("<<toc>>", &OrgParser::generate_toc(&site_page.toc));
+        let rendered = if page_template.contains("<<footnotes>>") {
+            rendered
+                .replace("<<footnotes>>", &footnotes)
+                .replace("<<page>>", &html)
+        } else {
+            rendered.replace("<<page>>", &format!("{}{}", html, footnotes))
+        };
+
+        if let Some(p) = handle_site_path(path, true)? {
+            File::create(&p)
+                .and_then(|mut f| f.write_all(rendered.as_bytes()))
+                .map_err(|e| ScrollError::io(Path::new(&p), e))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl SitePage {
+    fn new(path: &Path, ast: OrgDoc, date_format: &str) -> SitePage {
+        SitePage {
+            template: ast.template.clone(),
+            styles: ast.styles.clone(),
+            meta: PageMeta {
+                title: ast.title.to_string(),
+                summary: ast.summary.to_string(),
+                date: ast.date.to_string(),
+                formatted_date: ast
+                    .parsed_date
+                    .map(|d| d.format(date_format).to_string())
+                    .unwrap_or_else(|| ast.date.to_string()),
+                parsed_date: ast.parsed_date,
+                url: site_path(path, true)
+                    .map(|p| p.trim_start_matches("./public").to_string())
+                    .unwrap_or_default(),
+                tags: ast.tags.clone(),
+                section: path
+                    .parent()
+                    .and_then(|p| p.strip_prefix(".").ok())
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            },
+            ast: ast.ast,
+            footnotes: ast.footnotes,
+            toc: ast.toc,
+            anchors: ast.anchors,
+            includes: ast.includes,
+            sitemap: ast.sitemap,
+        }
+    }
+}
+
+// The `#+date:` of a page, or when its file was last modified for pages
+// without one.
+fn page_date(path: &Path, page: &SitePage) -> Option<NaiveDateTime> {
+    page.meta.parsed_date.or_else(|| {
+        let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+        Some(DateTime::<Utc>::from(modified).naive_utc())
+    })
+}
+
+fn is_page(path: &Path) -> bool {
+    !path.is_dir() && path.extension().is_some_and(|e| e == "org" || e == "md")
+}
+
+fn is_bl(entry: &DirEntry) -> bool {
+    entry
+        .file_name()
+        .to_str()
+        .map(|s| {
+            if s == "." {
+                false
+            } else {
+                BLACK_LIST.contains(&s) || s.starts_with('.') || s.starts_with('_')
+            }
+        })
+        .unwrap_or(false)
+}
+
+// `_part.org` or anything under `_includes/` is only there to be included,
+// pages still depend on it but it isn't published.
+fn is_unpublished(path: &Path) -> bool {
+    path.components()
+        .skip(1)
+        .any(|c| c.as_os_str().to_str().is_some_and(|s| s.starts_with('_')))
+}
+
+// Same rule as `is_bl`, for a path that didn't come from walking the site.
+// Editor leftovers like `.#page.org`, `page.org~`, `#page.org#` and swap
+// files are ignored too.
+fn is_ignored(path: &Path) -> bool {
+    let is_temp = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| {
+            n.ends_with('~')
+                || n.ends_with(".swp")
+                || n.ends_with(".swx")
+                || (n.starts_with('#') && n.ends_with('#'))
+        })
+        .unwrap_or(true);
+
+    is_temp
+        || path.components().skip(1).any(|c| {
+            c.as_os_str()
+                .to_str()
+                .map(|s| BLACK_LIST.contains(&s) || s.starts_with('.'))
+                .unwrap_or(true)
+        })
+}
+
+// Turns a path coming from the file watcher into the `./dir/file` form that
+// walking the site produces.
+fn site_relative_path(path: &Path) -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    let rel = path.strip_prefix(&cwd).unwrap_or(path);
+    if rel.is_absolute() {
+        return None;
+    }
+    let mut site_path = PathBuf::from(".");
+    for c in rel.components() {
+        match c {
+            std::path::Component::Normal(s) => site_path.push(s),
+            std::path::Component::CurDir => (),
+            _ => return None,
+        }
+    }
+    Some(site_path)
+}
+
+fn site_path(path: &Path, is_page: bool) -> Option<String> {
+    let mut new_path = String::from("./public/");
+    let p_str = path.to_str()?;
+    if is_page {
+        new_path.push_str(p_str.get(2..p_str.rfind('.').unwrap_or(p_str.len()))?);
+        new_path.push_str(".html");
+    } else {
+        new_path.push_str(p_str.get(2..)?);
+    }
+    Some(new_path)
+}
+
+fn handle_site_path(path: &Path, is_page: bool) -> Result<Option<String>, ScrollError> {
+    let new_path = match site_path(path, is_page) {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+
+    let dir = if path.is_dir() {
+        &new_path[..]
+    } else {
+        &new_path[..new_path.rfind('/').unwrap()]
+    };
+    fs::create_dir_all(dir).map_err(|e| ScrollError::io(Path::new(dir), e))?;
+
+    Ok(Some(new_path))
+}
@@ -0,0 +1,27 @@
+use crate::feed::escape;
+
+pub struct SitemapEntry {
+    pub url: String,
+    pub lastmod: Option<String>,
+}
+
+pub fn generate_sitemap(entries: &[SitemapEntry]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for entry in entries {
+        xml.push_str("<url>\n");
+        xml.push_str(&format!("<loc>{}</loc>\n", escape(&entry.url)));
+        if let Some(lastmod) = &entry.lastmod {
+            xml.push_str(&format!("<lastmod>{}</lastmod>\n", lastmod));
+        }
+        xml.push_str("</url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+pub fn generate_robots(sitemap_url: &str) -> String {
+    format!("User-agent: *\nAllow: /\n\nSitemap: {}\n", sitemap_url)
+}
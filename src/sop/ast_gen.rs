@@ -1,14 +1,18 @@
+use chrono::NaiveDateTime;
 use regex::Regex;
-use std::collections::HashSet;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
 
+use crate::sop::date;
 use crate::sop::html_gen::*;
 lazy_static! {
     pub static ref REGEX_ALL: Regex = Regex::new(
-        r"(?m)(?P<headline>^\*+ .*\n?)|(?P<list>^[ \t]*(?:-|\+|[ \t]+\*|\d+\.|\d+\)) .*\n?)|(?P<keyword>^[ \t]*#\+.*:.*\n)|(?P<table>^ *\|.*\n)|(?P<block>#\+(?i)begin(?-i)_.+\n(?:.*\n)*?\s*#\+(?i)end(?-i)_.+)"
-    ).unwrap();
-    static ref REGEX_TEXT: Regex = Regex::new(
-        r"(?m)(?P<bold>\*\w+(?:\s+\w+)*\*)|(?P<italic>/\w+(?:\s+\w+)*/)|(?P<code>~\w+(?:\s+\w+)*~)|(?P<underline>_\w+(?:\s+\w+)*_)|(?P<strike>\+\w+(?:\s+\w+)*\+)|(?P<link>\[\[.+?\]\])"
+        r"(?m)(?P<headline>^\*+ .*\n?(?:[ \t]*:(?i)properties(?-i):[ \t]*\n(?:[ \t]*:.*\n)*?[ \t]*:(?i)end(?-i):[ \t]*(?:\n|$))?)|(?P<list>^[ \t]*(?:-|\+|[ \t]+\*|\d+\.|\d+\)) .*\n?)|(?P<block>^[ \t]*#\+(?i)begin(?-i)_.+\n(?:.*\n)*?\s*#\+(?i)end(?-i)_.+)|(?P<results>^[ \t]*#\+(?i)results(?-i):.*\n(?:[ \t]*:(?:[ \t].*)?\n?)*)|(?P<keyword>^[ \t]*#\+.*:.*\n)|(?P<table>(?:^[ \t]*\|.*\n?)+)|(?P<footnote>^\[fn:[\w-]+\].*\n?)"
     ).unwrap();
+    static ref REGEX_BULLET: Regex = Regex::new(r"^(?:[ \t]*(?:[-+]|\d+[.)])|[ \t]+\*)(?:[ \t]|$)").unwrap();
+    static ref REGEX_TAGS: Regex = Regex::new(r"(?:^|[ \t]+)(:[\w@#%:]+:)[ \t]*$").unwrap();
+    static ref REGEX_TABLE_ALIGN: Regex = Regex::new(r"^<([lcr]?)\d*>$").unwrap();
     static ref IMG_TYPES: HashSet<&'static str> =
         vec!["apng", "bmp", "gif", "ico", "cur", "jpg", "jpeg", "jfif", "pjpeg", "pjp", "png", "svg", "tif", "tiff", "webp"].into_iter().collect();
 }
@@ -28,36 +32,89 @@ pub enum OrgElement {
         id: String,
         title: Vec<OrgElement>,
         is_section: bool,
+        meta: HeadlineMeta,
         style: Option<String>,
         section_style: Option<String>,
     },
     Block {
         block_type: BlockType,
-        params: String,
+        params: BlockParams,
         value: String,
         style: Option<String>,
     },
+    // a block whose contents are org themselves, like `#+begin_center`.
+    Container {
+        block_type: BlockType,
+        childs: Vec<OrgElement>,
+        style: Option<String>,
+    },
     List {
         list_type: ListType,
-        indentation: i8,
         items: Vec<OrgElement>,
     },
-    ListItem(Vec<OrgElement>, String),
+    ListItem {
+        // the item's first paragraph.
+        childs: Vec<OrgElement>,
+        checkbox: Option<Checkbox>,
+        // `[@5]`, where an ordered list starts counting again.
+        counter: Option<usize>,
+        // `term` of a `- term :: definition` item.
+        term: Option<Vec<OrgElement>>,
+        // what is indented under the item, paragraphs, blocks, lists...
+        contents: Vec<OrgElement>,
+    },
+    Table {
+        header: Vec<Vec<Vec<OrgElement>>>,
+        rows: Vec<Vec<Vec<OrgElement>>>,
+        align: Vec<Option<TableAlign>>,
+        style: Option<String>,
+    },
     Keyword {
         key: String,
         value: String,
     },
+    // `target` is the link as written, `link` where it points in the html,
+    // which can change once the rest of the site is known.
     Link {
         link_type: LinkType,
         link: String,
+        target: String,
         desc: Vec<OrgElement>,
     },
+    // `[fn:label]`, or with an inline definition `[fn:label: text]` and
+    // `[fn:: text]`. Numbers are given in order of first reference.
+    FootnoteRef {
+        label: String,
+        number: usize,
+        first: bool,
+        def: Option<Vec<OrgElement>>,
+    },
+    // `[fn:label] text` at the start of a line.
+    FootnoteDef {
+        label: String,
+        childs: Vec<OrgElement>,
+    },
+    // `#+toc: headlines [depth]`, entries are filled once the page is parsed.
+    Toc {
+        depth: Option<usize>,
+        entries: Vec<TocEntry>,
+    },
     Text(String),
-    Bold(String),
-    Italic(String),
-    Underline(String),
+    // raw html, from `@@html:...@@` export snippets or markdown inline html.
+    Html(String),
+    Bold(Vec<OrgElement>),
+    Italic(Vec<OrgElement>),
+    Underline(Vec<OrgElement>),
     Code(String),
-    StrikeThrough(String),
+    // `[2/3]` or `[66%]`, counted from the checkboxes or todo headlines
+    // under the item or headline it is in.
+    Cookie {
+        done: usize,
+        total: usize,
+        percent: bool,
+    },
+    Verbatim(String),
+    StrikeThrough(Vec<OrgElement>),
 }
 
 #[derive(Debug, PartialEq)]
@@ -65,17 +122,189 @@ pub enum BlockType {
     SRC,
     HTML,
     QUOTE,
-    UNDEFINED,
+    RESULTS,
+    EXAMPLE,
+    VERSE,
+    CENTER,
+    EXPORT,
+    COMMENT,
+    // any other `#+begin_<name>`, e.g. `#+begin_note`.
+    SPECIAL(String),
+}
+
+impl BlockType {
+    fn from_name(name: &str) -> BlockType {
+        match name {
+            "src" => BlockType::SRC,
+            "html" => BlockType::HTML,
+            "quote" => BlockType::QUOTE,
+            "example" => BlockType::EXAMPLE,
+            "verse" => BlockType::VERSE,
+            "center" => BlockType::CENTER,
+            "export" => BlockType::EXPORT,
+            "comment" => BlockType::COMMENT,
+            _ => BlockType::SPECIAL(name.to_string()),
+        }
+    }
+
+    // Whether the block's contents are parsed as org.
+    pub fn is_container(&self) -> bool {
+        matches!(
+            self,
+            BlockType::QUOTE | BlockType::CENTER | BlockType::SPECIAL(_)
+        )
+    }
+}
+
+// What follows `#+begin_<type>`, e.g. `python -n :exports both`.
+#[derive(Debug, Default, PartialEq)]
+pub struct BlockParams {
+    pub lang: String,
+    pub args: HashMap<String, String>,
+    pub switches: Vec<String>,
+    pub line_numbers: Option<LineNumbers>,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LineNumbers {
+    // `-n [start]`
+    NEW(usize),
+    // `+n [offset]`, continues from the previous numbered block.
+    CONTINUE(usize),
+}
+
+// What surrounds a headline's title, e.g. `TODO [#A] Title :draft:web:`,
+// and its property drawer.
+#[derive(Debug, Default)]
+pub struct HeadlineMeta {
+    pub todo: Option<String>,
+    pub priority: Option<char>,
+    pub tags: Vec<String>,
+    pub properties: HashMap<String, String>,
+}
+
+impl HeadlineMeta {
+    // Takes the todo keyword, priority and tags out of `title`.
+    fn parse(title: &str, drawer: &str) -> (HeadlineMeta, String) {
+        let mut meta = HeadlineMeta {
+            properties: parse_properties(drawer),
+            ..HeadlineMeta::default()
+        };
+        let mut title = title.trim();
+
+        for keyword in TODO_KEYWORDS {
+            if let Some(t) = title.strip_prefix(keyword) {
+                if t.is_empty() || t.starts_with(char::is_whitespace) {
+                    meta.todo = Some(keyword.to_string());
+                    title = t.trim_start();
+                    break;
+                }
+            }
+        }
+
+        if let Some(t) = title.strip_prefix("[#") {
+            let mut chars = t.chars();
+            if let (Some(p), Some(']')) = (chars.next(), chars.next()) {
+                if p.is_ascii_alphanumeric() {
+                    meta.priority = Some(p);
+                    title = t[2..].trim_start();
+                }
+            }
+        }
+
+        let mut title = title.to_string();
+        if let Some(c) = REGEX_TAGS.captures(&title) {
+            meta.tags = c[1]
+                .split(':')
+                .filter(|t| !t.is_empty())
+                .map(|t| t.to_string())
+                .collect();
+            title.truncate(c.get(0).unwrap().start());
+        }
+        (meta, title)
+    }
+}
+
+const TODO_KEYWORDS: [&str; 2] = ["TODO", "DONE"];
+
+// `-n`, `+n`, `-r`... a negative number isn't one.
+fn is_switch(word: &str) -> bool {
+    let mut chars = word.chars();
+    matches!(chars.next(), Some('-') | Some('+'))
+        && chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+}
+
+impl BlockParams {
+    pub fn parse(raw: &str) -> BlockParams {
+        let mut params = BlockParams::default();
+        let mut words = raw.split_whitespace().peekable();
+        if let Some(w) = words.peek() {
+            if !w.starts_with(&[':', '-', '+'][..]) {
+                params.lang = w.to_string();
+                words.next();
+            }
+        }
+
+        while let Some(word) = words.next() {
+            if let Some(key) = word.strip_prefix(':') {
+                // the value ends at the next `:key` or switch like `-n`.
+                let mut value = Vec::new();
+                while let Some(w) = words.peek() {
+                    if w.starts_with(':') || is_switch(w) {
+                        break;
+                    }
+                    value.push(*w);
+                    words.next();
+                }
+                params.args.insert(key.to_lowercase(), value.join(" "));
+            } else if word == "-n" || word == "+n" {
+                let n = words.peek().and_then(|w| w.parse::<usize>().ok());
+                if n.is_some() {
+                    words.next();
+                }
+                params.line_numbers = Some(if word == "-n" {
+                    LineNumbers::NEW(n.unwrap_or(1))
+                } else {
+                    LineNumbers::CONTINUE(n.unwrap_or(0))
+                });
+            } else {
+                params.switches.push(word.to_string());
+            }
+        }
+        params
+    }
+
+    // `:exports` of a src block, org exports only the code by default.
+    pub fn exports(&self) -> &str {
+        self.args.get("exports").map_or("code", |e| e.as_str())
+    }
 }
 #[derive(Debug, PartialEq)]
 pub enum LinkType {
     A,
     IMG,
 }
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Checkbox {
+    // `[X]`
+    ON,
+    // `[ ]`
+    OFF,
+    // `[-]`, some of its children are checked.
+    TRANS,
+}
 #[derive(Debug, PartialEq)]
 pub enum ListType {
     ORDERED,
     UNORDERED,
+    // `- term :: definition`
+    DESCRIPTION,
+}
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TableAlign {
+    LEFT,
+    CENTER,
+    RIGHT,
 }
 
 pub fn create_headline(
@@ -92,10 +321,13 @@ pub fn create_headline(
         break;
     }
 
+    // the headline line, then its property drawer if any.
+    let (line, drawer) = raw_value.split_once('\n').unwrap_or((raw_value, ""));
     let mut section_style = section_style;
     let mut is_section = false;
     let mut title = Vec::new();
-    if let Some(t) = raw_value.get((level + 1) as usize..) {
+    let mut meta = HeadlineMeta::default();
+    if let Some(t) = line.get((level + 1) as usize..) {
         if let Some(i) = t.to_lowercase().find("#+style:") {
             if i == 0 {
                 is_section = true;
@@ -106,22 +338,46 @@ pub fn create_headline(
                         .to_string(),
                 );
             } else {
-                title = handle_text(t.trim().to_owned());
+                (meta, title) = headline_title(t, drawer);
             }
         } else {
-            title = handle_text(t.trim().to_owned());
+            (meta, title) = headline_title(t, drawer);
         }
     }
     OrgElement::Headline {
         level,
-        id: generate_html_id(&title),
+        id: match meta.properties.get("CUSTOM_ID") {
+            Some(id) => id.to_string(),
+            None => generate_html_id(&title),
+        },
         title,
         is_section,
+        meta,
         style,
         section_style,
     }
 }
 
+fn headline_title(raw: &str, drawer: &str) -> (HeadlineMeta, Vec<OrgElement>) {
+    let (meta, title) = HeadlineMeta::parse(raw, drawer);
+    (meta, handle_text(title))
+}
+
+// `:KEY: value` lines of a `:PROPERTIES:` drawer, keys uppercased.
+fn parse_properties(drawer: &str) -> HashMap<String, String> {
+    let mut props = HashMap::new();
+    for line in drawer.lines() {
+        let line = line.trim();
+        if let Some((key, value)) = line.strip_prefix(':').and_then(|l| l.split_once(':')) {
+            let key = key.to_uppercase();
+            if key != "PROPERTIES" && key != "END" && !key.is_empty() {
+                props.insert(key, value.trim().to_string());
+            }
+        }
+    }
+    props
+}
+
 pub fn create_keyword(raw_value: &str) -> OrgElement {
     let key = raw_value.get(2..raw_value.find(':').unwrap()).unwrap();
     let val = raw_value.get(raw_value.find(':').unwrap() + 1..).unwrap();
@@ -131,8 +387,9 @@ pub fn create_keyword(raw_value: &str) -> OrgElement {
         value: val.trim().to_string(),
     }
 }
-pub fn create_block(raw_value: &str, style: Option<String>) -> OrgElement {
-    let mut block_type = BlockType::UNDEFINED;
+pub fn create_block(raw_value: &str, style: Option<String>, caption: Option<String>) -> OrgElement {
+    let indentation = raw_value.len() - raw_value.trim_start().len();
+    let raw_value = raw_value.trim_start();
     let fel = raw_value.find('\n').unwrap();
     let lel = raw_value.rfind('\n').unwrap();
     let mut sep = fel;
@@ -142,24 +399,105 @@ pub fn create_block(raw_value: &str, style: Option<String>) -> OrgElement {
         }
     }
     let t = raw_value.get(8..sep).unwrap().to_lowercase();
-    let params = raw_value.get(sep..fel).unwrap().to_string();
-    let value = raw_value.get(fel..lel).unwrap().to_string();
-
-    if t == "src" {
-        block_type = BlockType::SRC;
-    } else if t == "html" {
-        block_type = BlockType::HTML;
-    } else if t == "quote" {
-        block_type = BlockType::QUOTE;
+    let mut params = BlockParams::parse(raw_value.get(sep..fel).unwrap());
+    // the block's own indentation, e.g. in a list item, isn't content.
+    let value = raw_value
+        .get(fel + 1..lel)
+        .unwrap_or_default()
+        .lines()
+        .map(|l| {
+            let trimmed = l.trim_start_matches(&[' ', '\t'][..]);
+            l.get(indentation.min(l.len() - trimmed.len())..).unwrap()
+        })
+        .collect::<Vec<&str>>()
+        .join("\n");
+    if let Some(c) = caption {
+        params.args.entry("caption".to_string()).or_insert(c);
     }
 
     OrgElement::Block {
-        block_type,
+        block_type: BlockType::from_name(&t),
         params,
         value,
         style,
     }
 }
+// End of the block starting at `start`, after its matching `#+end_` line,
+// so blocks of the same type can be nested in special blocks.
+pub fn block_end(raw_str: &str, start: usize) -> Option<usize> {
+    let first_line = raw_str[start..].lines().next()?.trim().to_lowercase();
+    let name = first_line
+        .get("#+begin_".len()..)?
+        .split_whitespace()
+        .next()?
+        .to_string();
+    let begin = format!("#+begin_{}", name);
+    let end = format!("#+end_{}", name);
+
+    let mut depth = 0;
+    let mut pos = start;
+    for line in raw_str[start..].split_inclusive('\n') {
+        let l = line.trim().to_lowercase();
+        let is_name_end = |prefix: &str| {
+            l.starts_with(prefix)
+                && l[prefix.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+        };
+        if is_name_end(&begin) {
+            depth += 1;
+        } else if is_name_end(&end) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(pos + line.trim_end().len());
+            }
+        }
+        pos += line.len();
+    }
+    None
+}
+// A footnote definition goes on until an empty line, a headline or the next
+// definition.
+pub fn footnote_end(raw_str: &str, start: usize) -> usize {
+    let mut pos = start;
+    for (i, line) in raw_str[start..].split_inclusive('\n').enumerate() {
+        let l = line.trim();
+        if i > 0 && (l.is_empty() || l.starts_with("[fn:") || line.starts_with('*')) {
+            break;
+        }
+        pos += line.len();
+    }
+    pos
+}
+
+pub fn create_footnote(raw_value: &str) -> OrgElement {
+    let end = raw_value.find(']').unwrap();
+    let text: Vec<&str> = raw_value[end + 1..].split_whitespace().collect();
+    OrgElement::FootnoteDef {
+        label: raw_value["[fn:".len()..end].to_string(),
+        childs: handle_text(text.join(" ")),
+    }
+}
+
+// `#+RESULTS:` of a src block, either fixed-width `: ` lines or a block.
+pub fn create_results(raw_value: &str) -> OrgElement {
+    let body = raw_value.get(raw_value.find('\n').unwrap() + 1..).unwrap();
+    let value: Vec<&str> = body
+        .lines()
+        .map(|l| {
+            let l = l.trim_start().get(1..).unwrap_or_default();
+            l.strip_prefix(' ').unwrap_or(l)
+        })
+        .collect();
+
+    OrgElement::Block {
+        block_type: BlockType::RESULTS,
+        params: BlockParams::default(),
+        value: value.join("\n"),
+        style: None,
+    }
+}
 pub fn create_paragraph(raw_value: String, style: Option<String>) -> OrgElement {
     OrgElement::Paragraph {
         childs: handle_text(raw_value),
@@ -189,6 +527,10 @@ pub fn create_link(raw_value: &str) -> OrgElement {
             is_escape = false;
         }
     }
+    let target = link.clone();
+    if let Some(l) = link.strip_prefix("file:") {
+        link = l.to_string();
+    }
     if let Some(i) = link.rfind('.') {
         if (link.len() - i) - 1 < 6
             && IMG_TYPES.contains(&link.get(i + 1..).unwrap().to_lowercase()[..])
@@ -196,6 +538,7 @@ pub fn create_link(raw_value: &str) -> OrgElement {
             return OrgElement::Link {
                 link_type: LinkType::IMG,
                 link,
+                target,
                 desc: if desc.is_empty() {
                     Vec::new()
                 } else {
@@ -217,6 +560,7 @@ pub fn create_link(raw_value: &str) -> OrgElement {
     OrgElement::Link {
         link_type: LinkType::A,
         link,
+        target,
         desc: if desc.is_empty() {
             Vec::new()
         } else {
@@ -224,127 +568,513 @@ pub fn create_link(raw_value: &str) -> OrgElement {
         },
     }
 }
-pub fn create_list(raw_value: &str) -> OrgElement {
-    let mut list_type = ListType::ORDERED;
-    let mut indentation = 0i8;
-    for c in raw_value.chars() {
-        if c == '-' || c == '+' || c == '*' {
-            list_type = ListType::UNORDERED;
-            break;
+pub fn create_list(raw_value: &str, items: Vec<OrgElement>) -> OrgElement {
+    let list_type = match items.first() {
+        Some(OrgElement::ListItem { term: Some(_), .. }) => ListType::DESCRIPTION,
+        _ if raw_value
+            .trim_start()
+            .starts_with(|c: char| c.is_ascii_digit()) =>
+        {
+            ListType::ORDERED
         }
-        if c != ' ' {
-            break;
+        _ => ListType::UNORDERED,
+    };
+    OrgElement::List { list_type, items }
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches([' ', '\t']).len()
+}
+
+// End of the list starting at `start`: its items and every line indented
+// under them. Two blank lines, or a line not indented more than the bullets
+// that isn't an item, end it.
+pub fn list_end(raw_str: &str, start: usize) -> usize {
+    let base = indentation(&raw_str[start..]);
+    let mut end = start;
+    let mut blank_lines = 0;
+    let mut pos = start;
+    for line in raw_str[start..].split_inclusive('\n') {
+        if line.trim().is_empty() {
+            blank_lines += 1;
+            if blank_lines >= 2 {
+                break;
+            }
+        } else {
+            let ind = indentation(line);
+            if pos > start && (ind < base || (ind == base && !REGEX_BULLET.is_match(line))) {
+                break;
+            }
+            blank_lines = 0;
+            end = pos + line.len();
         }
-        indentation += 1;
+        pos += line.len();
     }
+    end
+}
 
-    OrgElement::List {
-        list_type,
-        indentation,
-        items: vec![create_list_item(raw_value)],
+// Splits a list into its items, with the line each one starts at, counted
+// from zero.
+pub fn split_list(raw_value: &str) -> Vec<(usize, &str)> {
+    let base = indentation(raw_value);
+    let mut items = Vec::new();
+    let mut item_start = 0;
+    let mut item_line = 0;
+    let mut pos = 0;
+    for (i, line) in raw_value.split_inclusive('\n').enumerate() {
+        if i > 0 && indentation(line) == base && REGEX_BULLET.is_match(line) {
+            items.push((item_line, &raw_value[item_start..pos]));
+            item_start = pos;
+            item_line = i;
+        }
+        pos += line.len();
     }
+    items.push((item_line, &raw_value[item_start..]));
+    items
 }
-fn add_to_list(it: &mut Vec<OrgElement>, ind: i8, c: OrgElement) {
-    let mut item_ind = 0i8;
 
-    if let OrgElement::ListItem(_, r) = &c {
-        for c in r.chars() {
-            if c != ' ' {
+// Returns the item without its first paragraph and contents, and the text
+// to parse for them: the rest of the bullet line followed by the lines
+// indented under it.
+pub fn create_list_item(raw_value: &str) -> (OrgElement, String) {
+    let (first_line, rest) = raw_value.split_once('\n').unwrap_or((raw_value, ""));
+    let item = first_line.trim();
+    // skips the bullet, `-`, `+`, `*`, `1.` or `1)`.
+    let mut text = item
+        .find(char::is_whitespace)
+        .map_or("", |i| item[i..].trim_start());
+
+    let ordered = item.starts_with(|c: char| c.is_ascii_digit());
+    let mut counter = None;
+    if let Some(t) = text.strip_prefix("[@") {
+        if let Some((n, t)) = t.split_once(']') {
+            if let Ok(n) = n.parse() {
+                counter = Some(n);
+                text = t.trim_start();
+            }
+        }
+    }
+
+    let mut checkbox = None;
+    for (mark, state) in [
+        ("[ ]", Checkbox::OFF),
+        ("[X]", Checkbox::ON),
+        ("[x]", Checkbox::ON),
+        ("[-]", Checkbox::TRANS),
+    ] {
+        if let Some(t) = text.strip_prefix(mark) {
+            if t.is_empty() || t.starts_with(char::is_whitespace) {
+                checkbox = Some(state);
+                text = t.trim_start();
                 break;
             }
-            item_ind += 1;
         }
     }
 
-    if let OrgElement::List {
-        indentation, items, ..
-    } = it.last_mut().unwrap()
-    {
-        if item_ind < *indentation {
-            it.push(c);
-        } else {
-            add_to_list(items, *indentation, c);
+    let mut term = None;
+    if !ordered {
+        if let Some((t, definition)) = text.split_once(" :: ") {
+            term = Some(handle_text(t.trim().to_string()));
+            text = definition.trim_start();
+        } else if let Some(t) = text.strip_suffix(" ::") {
+            term = Some(handle_text(t.trim().to_string()));
+            text = "";
         }
-    } else if item_ind <= ind {
-        it.push(c);
-    } else if let OrgElement::ListItem(_, r) = c {
-        it.push(create_list(&r))
     }
-}
 
-pub fn create_list_item(raw_value: &str) -> OrgElement {
-    OrgElement::ListItem(
-        handle_text(raw_value.trim().get(2..).unwrap().to_string()),
-        raw_value.to_string(),
+    let ind = rest
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(indentation)
+        .min()
+        .unwrap_or(1);
+    let mut body = format!("{}\n", text);
+    for line in rest.lines() {
+        if line.trim().is_empty() {
+            body.push('\n');
+            continue;
+        }
+        // a `*` bullet keeps a space, not to be taken for a headline.
+        let line = &line[ind..];
+        if line.starts_with('*') {
+            body.push(' ');
+        }
+        body.push_str(line);
+        body.push('\n');
+    }
+
+    (
+        OrgElement::ListItem {
+            childs: Vec::new(),
+            checkbox,
+            counter,
+            term,
+            contents: Vec::new(),
+        },
+        body,
     )
 }
 
+// Checked and total checkboxes directly in `items`.
+fn checkbox_stats(items: &[OrgElement]) -> (usize, usize) {
+    let mut stats = (0, 0);
+    for item in items {
+        if let OrgElement::ListItem {
+            checkbox: Some(c), ..
+        } = item
+        {
+            stats.1 += 1;
+            if *c == Checkbox::ON {
+                stats.0 += 1;
+            }
+        }
+    }
+    stats
+}
+
+// Done and total todo headlines directly in a section, or its top level
+// checkboxes when it has no todo headline.
+fn section_stats(childs: &[OrgElement]) -> (usize, usize) {
+    let mut stats = (0, 0);
+    for el in childs {
+        if let OrgElement::Headline {
+            meta: HeadlineMeta { todo: Some(t), .. },
+            ..
+        } = el
+        {
+            stats.1 += 1;
+            if t == "DONE" {
+                stats.0 += 1;
+            }
+        }
+    }
+    if stats.1 > 0 {
+        return stats;
+    }
+    for el in childs {
+        if let OrgElement::List { items, .. } = el {
+            let (done, total) = checkbox_stats(items);
+            stats = (stats.0 + done, stats.1 + total);
+        }
+    }
+    stats
+}
+
+fn set_cookies(texts: &mut [OrgElement], stats: (usize, usize)) {
+    for el in texts {
+        walk_mut(el, &mut |e| {
+            if let OrgElement::Cookie { done, total, .. } = e {
+                (*done, *total) = stats;
+            }
+        });
+    }
+}
+
+// Fills the cookies of items and headlines from what is under them, and
+// sets the checkbox of items whose children have checkboxes. Children are
+// updated first, so their state counts for their parent.
+fn update_cookies(childs: &mut [OrgElement]) {
+    for el in childs.iter_mut() {
+        match el {
+            OrgElement::Section { childs, .. } | OrgElement::Container { childs, .. } => {
+                update_cookies(childs)
+            }
+            OrgElement::List { items, .. } => update_cookies(items),
+            OrgElement::ListItem {
+                childs,
+                checkbox,
+                contents,
+                ..
+            } => {
+                update_cookies(contents);
+                let mut stats = None;
+                for el in contents.iter() {
+                    if let OrgElement::List { items, .. } = el {
+                        let (done, total) = checkbox_stats(items);
+                        let (d, t) = stats.unwrap_or((0, 0));
+                        stats = Some((d + done, t + total));
+                    }
+                }
+                if let Some(stats) = stats {
+                    set_cookies(childs, stats);
+                    if checkbox.is_some() && stats.1 > 0 {
+                        *checkbox = Some(match stats.0 {
+                            0 => Checkbox::OFF,
+                            d if d == stats.1 => Checkbox::ON,
+                            _ => Checkbox::TRANS,
+                        });
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    for i in 0..childs.len().saturating_sub(1) {
+        if let OrgElement::Section {
+            childs: section, ..
+        } = &childs[i + 1]
+        {
+            let stats = section_stats(section);
+            if let OrgElement::Headline { title, .. } = &mut childs[i] {
+                set_cookies(title, stats);
+            }
+        }
+    }
+}
+
+pub fn create_table(raw_value: &str, style: Option<String>) -> OrgElement {
+    let mut rows: Vec<Vec<Vec<OrgElement>>> = Vec::new();
+    let mut align: Vec<Option<TableAlign>> = Vec::new();
+    let mut header_len: Option<usize> = None;
+
+    for line in raw_value.lines() {
+        let line = line.trim();
+        if line.starts_with("|-") {
+            if header_len.is_none() && !rows.is_empty() {
+                header_len = Some(rows.len());
+            }
+            continue;
+        }
+
+        let line = line.get(1..).unwrap();
+        let line = line.strip_suffix('|').unwrap_or(line);
+        let cells: Vec<&str> = line.split('|').map(|c| c.trim()).collect();
+
+        // a row made only of alignment cookies like `<l>`, `<c10>` or `<r>`
+        // is not rendered, it only sets the alignment of its columns.
+        if cells.iter().any(|c| !c.is_empty())
+            && cells
+                .iter()
+                .all(|c| c.is_empty() || REGEX_TABLE_ALIGN.is_match(c))
+        {
+            align = cells
+                .iter()
+                .map(|c| {
+                    REGEX_TABLE_ALIGN.captures(c).and_then(|cap| {
+                        match cap.get(1).unwrap().as_str() {
+                            "l" => Some(TableAlign::LEFT),
+                            "c" => Some(TableAlign::CENTER),
+                            "r" => Some(TableAlign::RIGHT),
+                            _ => None,
+                        }
+                    })
+                })
+                .collect();
+            continue;
+        }
+
+        rows.push(cells.iter().map(|c| handle_text(c.to_string())).collect());
+    }
+
+    let header = match header_len {
+        Some(l) if l < rows.len() => rows.drain(..l).collect(),
+        _ => Vec::new(),
+    };
+
+    OrgElement::Table {
+        header,
+        rows,
+        align,
+        style,
+    }
+}
+
+// Inline markup following org's emphasis rules: a marker opens only after
+// whitespace, `-('"{` or the start of the text, closes only before
+// whitespace, `-.,:!?;'")}\[` or the end, and the text right inside the
+// markers can't be whitespace. Code and verbatim aren't parsed further, other
+// emphasis can be nested like `*bold /italic/*`.
 pub fn handle_text(raw_value: String) -> Vec<OrgElement> {
+    let chars: Vec<char> = raw_value.chars().collect();
+    parse_inline(&chars)
+}
+
+fn parse_inline(chars: &[char]) -> Vec<OrgElement> {
     let mut texts: Vec<OrgElement> = Vec::new();
-    let mut cur_index = 0;
-    for cap in REGEX_TEXT.captures_iter(&raw_value) {
-        if let Some(c) = cap.name("bold") {
-            texts.push(OrgElement::Text(
-                raw_value.get(cur_index..c.start()).unwrap().to_owned(),
-            ));
-            texts.push(OrgElement::Bold(
-                c.as_str().get(1..c.as_str().len() - 1).unwrap().to_owned(),
-            ));
-            cur_index = c.end();
-        }
-        if let Some(c) = cap.name("italic") {
-            texts.push(OrgElement::Text(
-                raw_value.get(cur_index..c.start()).unwrap().to_owned(),
-            ));
-            texts.push(OrgElement::Italic(
-                c.as_str().get(1..c.as_str().len() - 1).unwrap().to_owned(),
-            ));
-            cur_index = c.end();
-        }
-        if let Some(c) = cap.name("code") {
-            texts.push(OrgElement::Text(
-                raw_value.get(cur_index..c.start()).unwrap().to_owned(),
-            ));
-            texts.push(OrgElement::Code(
-                c.as_str().get(1..c.as_str().len() - 1).unwrap().to_owned(),
-            ));
-            cur_index = c.end();
-        }
-        if let Some(c) = cap.name("underline") {
-            texts.push(OrgElement::Text(
-                raw_value.get(cur_index..c.start()).unwrap().to_owned(),
-            ));
-            texts.push(OrgElement::Underline(
-                c.as_str().get(1..c.as_str().len() - 1).unwrap().to_owned(),
-            ));
-            cur_index = c.end();
-        }
-        if let Some(c) = cap.name("strike") {
-            texts.push(OrgElement::Text(
-                raw_value.get(cur_index..c.start()).unwrap().to_owned(),
-            ));
-            texts.push(OrgElement::StrikeThrough(
-                c.as_str().get(1..c.as_str().len() - 1).unwrap().to_owned(),
-            ));
-            cur_index = c.end();
-        }
-        if let Some(c) = cap.name("link") {
-            if let Some(t) = raw_value.get(cur_index..c.start()) {
-                if !t.is_empty() {
-                    texts.push(OrgElement::Text(t.to_owned()))
+    let mut text = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\\' && chars.get(i + 1).is_some_and(|n| is_escapable(*n)) {
+            text.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+
+        let element = if starts_with(chars, i, "[fn:") {
+            find_bracket_end(chars, i).map(|end| {
+                let body: String = chars[i + 4..end].iter().collect();
+                let el = match body.find(':') {
+                    Some(c) => OrgElement::FootnoteRef {
+                        label: body[..c].to_string(),
+                        number: 0,
+                        first: false,
+                        def: Some(handle_text(body[c + 1..].trim().to_string())),
+                    },
+                    None => OrgElement::FootnoteRef {
+                        label: body,
+                        number: 0,
+                        first: false,
+                        def: None,
+                    },
+                };
+                (el, end + 1)
+            })
+        } else if starts_with(chars, i, "[[") {
+            find(chars, i + 2, "]]").map(|end| {
+                let raw: String = chars[i..end + 2].iter().collect();
+                (create_link(&raw), end + 2)
+            })
+        } else if starts_with(chars, i, "@@html:") {
+            find(chars, i + 7, "@@").map(|end| {
+                let html: String = chars[i + 7..end].iter().collect();
+                (OrgElement::Html(html), end + 2)
+            })
+        } else if c == '[' {
+            parse_cookie(chars, i)
+        } else if is_marker(c) && (i == 0 || is_pre(chars[i - 1])) {
+            find_closing(chars, i).map(|end| {
+                let body = &chars[i + 1..end];
+                let el = match c {
+                    '*' => OrgElement::Bold(parse_inline(body)),
+                    '/' => OrgElement::Italic(parse_inline(body)),
+                    '_' => OrgElement::Underline(parse_inline(body)),
+                    '+' => OrgElement::StrikeThrough(parse_inline(body)),
+                    '~' => OrgElement::Code(body.iter().collect()),
+                    _ => OrgElement::Verbatim(body.iter().collect()),
+                };
+                (el, end + 1)
+            })
+        } else {
+            None
+        };
+
+        match element {
+            Some((el, next)) => {
+                if !text.is_empty() {
+                    texts.push(OrgElement::Text(std::mem::take(&mut text)));
                 }
+                texts.push(el);
+                i = next;
+            }
+            None => {
+                text.push(c);
+                i += 1;
             }
-            texts.push(create_link(c.as_str()));
-            cur_index = c.end();
         }
     }
+    if !text.is_empty() {
+        texts.push(OrgElement::Text(text));
+    }
+    texts
+}
+
+// `[/]`, `[%]`, or ones already filled like `[1/3]` and `[33%]`.
+fn parse_cookie(chars: &[char], start: usize) -> Option<(OrgElement, usize)> {
+    let end = start + 1 + chars[start + 1..].iter().take(12).position(|c| *c == ']')?;
+    let body: String = chars[start + 1..end].iter().collect();
+    let number = |n: &str| -> Option<usize> {
+        if n.is_empty() {
+            Some(0)
+        } else if n.chars().all(|c| c.is_ascii_digit()) {
+            n.parse().ok()
+        } else {
+            None
+        }
+    };
 
-    if let Some(t) = raw_value.get(cur_index..) {
-        if !t.is_empty() {
-            texts.push(OrgElement::Text(t.to_owned()))
+    let el = if let Some(p) = body.strip_suffix('%') {
+        OrgElement::Cookie {
+            done: number(p)?,
+            total: 100,
+            percent: true,
+        }
+    } else {
+        let (done, total) = body.split_once('/')?;
+        OrgElement::Cookie {
+            done: number(done)?,
+            total: number(total)?,
+            percent: false,
         }
+    };
+    Some((el, end + 1))
+}
+
+// Index of the marker closing the one at `start`, links in between are
+// skipped so `*see [[a*b]]*` stays one bold text.
+fn find_closing(chars: &[char], start: usize) -> Option<usize> {
+    let marker = chars[start];
+    if chars.get(start + 1).is_none_or(|c| c.is_whitespace()) {
+        return None;
     }
-    texts
+    let verbatim = marker == '~' || marker == '=';
+    let mut i = start + 2;
+    while i < chars.len() {
+        if !verbatim && starts_with(chars, i, "[[") {
+            if let Some(end) = find(chars, i + 2, "]]") {
+                i = end + 2;
+                continue;
+            }
+        }
+        if !verbatim && chars[i] == '\\' {
+            i += 2;
+            continue;
+        }
+        if chars[i] == marker
+            && !chars[i - 1].is_whitespace()
+            && chars.get(i + 1).is_none_or(|c| is_post(*c))
+        {
+            return Some(i);
+        }
+        i += 1;
+    }
+    None
+}
+
+// Index of the `]` closing the `[` at `start`.
+fn find_bracket_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in chars.iter().enumerate().skip(start) {
+        match c {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+fn is_marker(c: char) -> bool {
+    matches!(c, '*' | '/' | '_' | '+' | '~' | '=')
+}
+
+fn is_pre(c: char) -> bool {
+    c.is_whitespace() || "-('\"{".contains(c)
+}
+
+fn is_post(c: char) -> bool {
+    c.is_whitespace() || "-.,:!?;'\")}\\[".contains(c)
+}
+
+fn is_escapable(c: char) -> bool {
+    is_marker(c) || matches!(c, '\\' | '[' | ']' | '@')
+}
+
+fn starts_with(chars: &[char], i: usize, s: &str) -> bool {
+    s.chars()
+        .enumerate()
+        .all(|(j, c)| chars.get(i + j) == Some(&c))
+}
+
+// Index of the next `s` at or after `from`.
+fn find(chars: &[char], from: usize, s: &str) -> Option<usize> {
+    (from..chars.len()).find(|&i| starts_with(chars, i, s))
 }
 
 pub fn handle_style(begin: usize, raw_str: &str) -> Option<String> {
@@ -376,6 +1106,21 @@ pub fn handle_style(begin: usize, raw_str: &str) -> Option<String> {
     None
 }
 
+// `#+caption:` among the keyword lines right above `begin`.
+pub fn handle_caption(begin: usize, raw_str: &str) -> Option<String> {
+    let before = raw_str.get(..begin)?;
+    for line in before.lines().rev() {
+        let line = line.trim();
+        if !line.starts_with("#+") {
+            break;
+        }
+        if line.to_lowercase().starts_with("#+caption:") {
+            return Some(line["#+caption:".len()..].trim().to_string());
+        }
+    }
+    None
+}
+
 pub fn handle_section_style(begin: usize, raw_str: &str) -> Option<String> {
     if let Some(s) = raw_str.get(begin..) {
         if let Some(i) = s.find('\n') {
@@ -394,6 +1139,116 @@ pub fn handle_section_style(begin: usize, raw_str: &str) -> Option<String> {
     None
 }
 
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    pub line: Option<usize>,
+    pub msg: String,
+}
+
+// Calls `f` on `el` and every element under it.
+pub fn walk_mut<F: FnMut(&mut OrgElement)>(el: &mut OrgElement, f: &mut F) {
+    f(el);
+    let childs = match el {
+        OrgElement::Section { childs, .. }
+        | OrgElement::Paragraph { childs, .. }
+        | OrgElement::Container { childs, .. }
+        | OrgElement::Headline { title: childs, .. }
+        | OrgElement::List { items: childs, .. }
+        | OrgElement::Link { desc: childs, .. }
+        | OrgElement::Bold(childs)
+        | OrgElement::Italic(childs)
+        | OrgElement::Underline(childs)
+        | OrgElement::StrikeThrough(childs) => childs,
+        OrgElement::ListItem {
+            childs,
+            term,
+            contents,
+            ..
+        } => {
+            for c in childs
+                .iter_mut()
+                .chain(term.iter_mut().flatten())
+                .chain(contents)
+            {
+                walk_mut(c, f);
+            }
+            return;
+        }
+        OrgElement::Table { header, rows, .. } => {
+            for cell in header.iter_mut().chain(rows.iter_mut()).flatten() {
+                for c in cell {
+                    walk_mut(c, f);
+                }
+            }
+            return;
+        }
+        _ => return,
+    };
+    for c in childs {
+        walk_mut(c, f);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TocEntry {
+    pub title: String,
+    pub id: String,
+    pub level: u8,
+    pub children: Vec<TocEntry>,
+}
+
+// Nests headlines under the previous one of a lower level, leaving out
+// those deeper than `depth` levels.
+fn toc_tree(headlines: &[(u8, String, String)], depth: Option<usize>) -> Vec<TocEntry> {
+    let top = match headlines.iter().map(|h| h.0).min() {
+        Some(l) => l,
+        None => return Vec::new(),
+    };
+    let mut stack: Vec<TocEntry> = Vec::new();
+    let mut toc: Vec<TocEntry> = Vec::new();
+    fn close(stack: &mut Vec<TocEntry>, toc: &mut Vec<TocEntry>) {
+        let entry = stack.pop().unwrap();
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(entry),
+            None => toc.push(entry),
+        }
+    }
+
+    for (level, id, title) in headlines {
+        if depth.is_some_and(|d| (*level - top) as usize >= d) {
+            continue;
+        }
+        while stack.last().is_some_and(|e| e.level >= *level) {
+            close(&mut stack, &mut toc);
+        }
+        stack.push(TocEntry {
+            title: title.to_string(),
+            id: id.to_string(),
+            level: *level,
+            children: Vec::new(),
+        });
+    }
+    while !stack.is_empty() {
+        close(&mut stack, &mut toc);
+    }
+    toc
+}
+
+// A headline other pages can link to, by title, `:CUSTOM_ID:` or `:ID:`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anchor {
+    pub title: String,
+    pub id: String,
+    pub custom_id: Option<String>,
+    pub org_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Footnote {
+    pub number: usize,
+    pub childs: Vec<OrgElement>,
+}
+
 #[derive(Debug)]
 pub struct OrgDoc {
     pub ast: OrgElement,
@@ -401,11 +1256,32 @@ pub struct OrgDoc {
     pub title: String,
     pub summary: String,
     pub date: String,
+    pub parsed_date: Option<NaiveDateTime>,
     pub template: Option<String>,
+    pub tags: Vec<String>,
+    pub footnotes: Vec<Footnote>,
+    // headlines up to `#+options: toc:N`, empty with `toc:nil`.
+    pub toc: Vec<TocEntry>,
+    pub anchors: Vec<Anchor>,
+    // files pulled in with `#+include:`.
+    pub includes: Vec<PathBuf>,
+    pub sitemap: bool,
+    pub errors: Vec<ParseError>,
     last_element_index: usize,
     depth: u8,
     section_stack: Vec<u8>,
-    list_indentation: i8,
+    // `:exports` of the src block just added, for its `#+RESULTS:`.
+    src_exports: Option<String>,
+    last_line_number: usize,
+    footnote_defs: HashMap<String, Vec<OrgElement>>,
+    headlines: Vec<(u8, String, String)>,
+    toc_depth: Option<usize>,
+    // ids already given to headlines, to keep them unique.
+    ids: HashSet<String>,
+    // subtrees with one of these tags are left out, `#+exclude_tags:`.
+    exclude_tags: Vec<String>,
+    // level of the excluded headline whose subtree is being skipped.
+    skip_level: Option<u8>,
 }
 impl Default for OrgDoc {
     fn default() -> Self {
@@ -424,16 +1300,62 @@ impl OrgDoc {
             title: String::new(),
             summary: String::new(),
             date: String::new(),
+            parsed_date: None,
             template: None,
+            tags: Vec::new(),
+            footnotes: Vec::new(),
+            toc: Vec::new(),
+            anchors: Vec::new(),
+            includes: Vec::new(),
+            sitemap: true,
+            errors: Vec::new(),
             last_element_index: 0,
             depth: 0,
             section_stack: Vec::new(),
-            list_indentation: 0,
+            src_exports: None,
+            last_line_number: 0,
+            footnote_defs: HashMap::new(),
+            headlines: Vec::new(),
+            toc_depth: None,
+            ids: HashSet::new(),
+            exclude_tags: vec!["noexport".to_string()],
+            skip_level: None,
+        }
+    }
+    // Whether `child` belongs to a subtree tagged with an excluded tag.
+    fn skips(&mut self, child: &OrgElement) -> bool {
+        match child {
+            OrgElement::Headline { level, meta, .. } => {
+                if self.skip_level.is_some_and(|l| *level > l) {
+                    return true;
+                }
+                self.skip_level = None;
+                if meta.tags.iter().any(|t| self.exclude_tags.contains(t)) {
+                    self.skip_level = Some(*level);
+                    return true;
+                }
+                false
+            }
+            // definitions can be referenced from outside the subtree.
+            OrgElement::FootnoteDef { .. } => false,
+            _ => self.skip_level.is_some(),
         }
     }
+
+    // `id`, or `id-1`, `id-2`... when a previous headline already took it.
+    fn unique_id(&mut self, id: &str) -> String {
+        let mut unique = id.to_string();
+        let mut n = 0;
+        while self.ids.contains(&unique) {
+            n += 1;
+            unique = format!("{}-{}", id, n);
+        }
+        self.ids.insert(unique.clone());
+        unique
+    }
+
     pub fn handle_undetect_str(&mut self, start: usize, end: usize, raw_str: &str) {
         let mut cur_parag = String::new();
-        let mut em_lns = 0u8;
 
         let mut style = if self.last_element_index > 0 {
             handle_style(self.last_element_index, raw_str)
@@ -442,24 +1364,24 @@ impl OrgDoc {
         };
 
         if let Some(s) = raw_str.get(self.last_element_index..start) {
-            for line in s.lines() {
-                if line == "" {
-                    em_lns += 1;
-                    if em_lns >= 2 {
-                        em_lns = 0;
-                        self.list_indentation = -1;
-                    }
-                    if !cur_parag.is_empty() {
-                        cur_parag.pop();
+            let first_line = raw_str[..self.last_element_index].matches('\n').count() + 1;
+            for (i, line) in s.lines().enumerate() {
+                if line.trim_start().to_lowercase().starts_with("#+begin_") {
+                    self.errors.push(ParseError {
+                        line: Some(first_line + i),
+                        msg: format!("\"{}\" block has no matching #+end_ line", line.trim()),
+                    });
+                }
+                if line == "" && !cur_parag.is_empty() {
+                    cur_parag.pop();
 
-                        self.add_child(create_paragraph(cur_parag.clone(), style.clone()));
+                    self.add_child(create_paragraph(cur_parag.clone(), style.clone()));
 
-                        cur_parag.clear();
-                        if style != None {
-                            style = None;
-                        }
-                        continue;
+                    cur_parag.clear();
+                    if style != None {
+                        style = None;
                     }
+                    continue;
                 }
                 if line != "" {
                     cur_parag.push_str(line);
@@ -474,7 +1396,127 @@ impl OrgDoc {
         self.last_element_index = end;
     }
 
+    // Takes in a document parsed from the contents of a block starting at
+    // `line`, returning its elements.
+    pub fn add_nested(&mut self, nested: OrgDoc, line: usize) -> Vec<OrgElement> {
+        self.styles.extend(nested.styles);
+        self.errors
+            .extend(nested.errors.into_iter().map(|e| ParseError {
+                line: e.line.map(|l| l + line),
+                msg: e.msg,
+            }));
+        for (label, childs) in nested.footnote_defs {
+            self.footnote_defs.entry(label).or_insert(childs);
+        }
+        match nested.ast {
+            OrgElement::Section { childs, .. } => childs,
+            _ => Vec::new(),
+        }
+    }
+
+    // Numbers footnote references in order of appearance and collects their
+    // definitions, once the whole page is parsed.
+    pub fn number_footnotes(&mut self) {
+        let mut labels: Vec<String> = Vec::new();
+        let mut defs = std::mem::take(&mut self.footnote_defs);
+        walk_mut(&mut self.ast, &mut |el| {
+            if let OrgElement::FootnoteRef {
+                label,
+                number,
+                first,
+                def,
+            } = el
+            {
+                if label.is_empty() {
+                    *label = format!("anonymous-{}", labels.len() + 1);
+                }
+                *first = !labels.contains(label);
+                if *first {
+                    labels.push(label.to_string());
+                }
+                *number = labels.iter().position(|l| l == label).unwrap() + 1;
+                if let Some(d) = def.take() {
+                    defs.entry(label.to_string()).or_insert(d);
+                }
+            }
+        });
+
+        for (i, label) in labels.iter().enumerate() {
+            match defs.remove(label) {
+                Some(childs) => self.footnotes.push(Footnote {
+                    number: i + 1,
+                    childs,
+                }),
+                None => {
+                    self.errors.push(ParseError {
+                        line: None,
+                        msg: format!("footnote \"{}\" has no definition", label),
+                    });
+                    self.footnotes.push(Footnote {
+                        number: i + 1,
+                        childs: Vec::new(),
+                    });
+                }
+            }
+        }
+    }
+
+    pub fn update_cookies(&mut self) {
+        if let OrgElement::Section { childs, .. } = &mut self.ast {
+            update_cookies(childs);
+        }
+    }
+
+    pub fn build_toc(&mut self) {
+        self.toc = toc_tree(&self.headlines, self.toc_depth);
+        let headlines = &self.headlines;
+        walk_mut(&mut self.ast, &mut |el| {
+            if let OrgElement::Toc { depth, entries } = el {
+                *entries = toc_tree(headlines, *depth);
+            }
+        });
+    }
+
+    // Whether the `#+RESULTS:` following the last element are exported.
+    pub fn results_exported(&self) -> bool {
+        matches!(self.src_exports.as_deref(), Some("results") | Some("both"))
+    }
+
     pub fn add_child(&mut self, child: OrgElement) {
+        let mut child = child;
+        if self.skips(&child) {
+            return;
+        }
+        self.src_exports = None;
+        if let OrgElement::Block {
+            block_type: BlockType::SRC,
+            params,
+            value,
+            ..
+        } = &mut child
+        {
+            if let Some(LineNumbers::CONTINUE(offset)) = params.line_numbers {
+                params.line_numbers = Some(LineNumbers::NEW(self.last_line_number + offset + 1));
+            }
+            if let Some(LineNumbers::NEW(start)) = params.line_numbers {
+                self.last_line_number = start + value.lines().count().max(1) - 1;
+            }
+            let exports = params.exports().to_string();
+            self.src_exports = Some(exports.clone());
+            if exports == "results" || exports == "none" {
+                return;
+            }
+        }
+
+        if let OrgElement::Headline {
+            id,
+            is_section: false,
+            ..
+        } = &mut child
+        {
+            *id = self.unique_id(id);
+        }
+
         if let OrgElement::Headline { level, .. } = child {
             while let Some(s) = self.section_stack.last() {
                 if *s >= level {
@@ -486,6 +1528,11 @@ impl OrgDoc {
             }
         }
 
+        if let OrgElement::FootnoteDef { label, childs } = child {
+            self.footnote_defs.entry(label).or_insert(childs);
+            return;
+        }
+
         let mut s = &mut self.ast;
 
         for _ in 0..self.depth {
@@ -497,10 +1544,25 @@ impl OrgDoc {
             match &child {
                 OrgElement::Headline {
                     level,
+                    id,
+                    title,
+                    meta,
                     section_style,
                     is_section,
                     ..
                 } => {
+                    if !is_section {
+                        // a cookie or keyword taken out of the title leaves
+                        // its space behind.
+                        let title = text_content(title).trim().to_string();
+                        self.headlines.push((*level, id.to_string(), title.clone()));
+                        self.anchors.push(Anchor {
+                            title,
+                            id: id.to_string(),
+                            custom_id: meta.properties.get("CUSTOM_ID").cloned(),
+                            org_id: meta.properties.get("ID").cloned(),
+                        });
+                    }
                     let s_style = section_style.clone();
                     self.section_stack.push(*level);
                     self.depth += 1;
@@ -517,73 +1579,51 @@ impl OrgDoc {
                         style: s_style,
                     });
                 }
-                OrgElement::ListItem(_, r) => {
-                    let mut ind = 0i8;
-                    for c in r.chars() {
-                        if c != ' ' {
-                            break;
-                        }
-                        ind += 1;
-                    }
-                    if self.list_indentation == -1 {
-                        v.push(create_list(r));
-                        self.list_indentation = ind;
-                        return;
-                    }
-                    if let Some(el) = v.last_mut() {
-                        if let OrgElement::List { items, .. } = el {
-                            add_to_list(items, self.list_indentation, child);
-                        } else {
-                            v.push(create_list(r))
-                        }
-                    } else {
-                        v.push(create_list(r))
-                    }
-                    self.list_indentation = ind;
-                }
-                OrgElement::Paragraph { childs, .. } => {
-                    if self.list_indentation == -1 {
-                        v.push(child);
-                        return;
-                    }
-                    let mut ind = 0i8;
-                    if let OrgElement::Text(s) = &childs[0] {
-                        for c in s.chars() {
-                            if c != ' ' {
-                                break;
-                            }
-                            ind += 1;
-                        }
-                    }
-
-                    if ind > self.list_indentation {
-                        if let Some(el) = v.last_mut() {
-                            let mut last_el = el;
-                            while let OrgElement::List { items, .. } = last_el {
-                                if let OrgElement::List { .. } = items.last().unwrap() {
-                                    last_el = items.last_mut().unwrap();
-                                    continue;
-                                }
-                                items.push(child);
-                                return;
-                            }
-                        }
-                    }
-                    v.push(child);
-                }
                 OrgElement::Keyword { key, value } => {
                     if key == "STYLE" {
                         for val in value.split_whitespace() {
                             self.styles.insert(val.to_string());
                         }
+                    } else if key == "EXCLUDE_TAGS" {
+                        self.exclude_tags =
+                            value.split_whitespace().map(|t| t.to_string()).collect();
                     } else if key == "TITLE" {
                         self.title = value.to_string();
                     } else if key == "DATE" {
                         self.date = value.to_string();
+                        self.parsed_date = date::parse_date(value);
                     } else if key == "SUMMARY" {
                         self.summary = value.to_string();
                     } else if key == "TEMPLATE" {
                         self.template = Some(value.to_string());
+                    } else if key == "OPTIONS" {
+                        for option in value.split_whitespace() {
+                            if let Some(toc) = option.strip_prefix("toc:") {
+                                self.toc_depth = match toc {
+                                    "nil" => Some(0),
+                                    "t" => None,
+                                    n => n.parse().ok(),
+                                };
+                            }
+                        }
+                    } else if key == "TOC" {
+                        let mut words = value.split_whitespace();
+                        if words.next() == Some("headlines") {
+                            v.push(OrgElement::Toc {
+                                depth: words.next().and_then(|d| d.parse().ok()),
+                                entries: Vec::new(),
+                            });
+                            return;
+                        }
+                    } else if key == "SITEMAP" {
+                        self.sitemap = !value.trim().eq_ignore_ascii_case("no");
+                    } else if key == "TAGS" || key == "FILETAGS" {
+                        for tag in value.split(|c: char| c == ':' || c == ',' || c.is_whitespace())
+                        {
+                            if !tag.is_empty() && !self.tags.iter().any(|t| t == tag) {
+                                self.tags.push(tag.to_string());
+                            }
+                        }
                     }
                     v.push(child);
                 }
@@ -592,3 +1632,72 @@ impl OrgDoc {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The inline elements of `raw` written back as `b(...)`, `i(...)`,
+    // `code(...)` etc. to compare them easily.
+    fn inline(raw: &str) -> String {
+        fn show(els: &[OrgElement]) -> String {
+            els.iter()
+                .map(|el| match el {
+                    OrgElement::Text(t) => t.clone(),
+                    OrgElement::Bold(c) => format!("b({})", show(c)),
+                    OrgElement::Italic(c) => format!("i({})", show(c)),
+                    OrgElement::Underline(c) => format!("u({})", show(c)),
+                    OrgElement::StrikeThrough(c) => format!("s({})", show(c)),
+                    OrgElement::Code(c) => format!("code({})", c),
+                    OrgElement::Verbatim(v) => format!("verb({})", v),
+                    el => format!("{:?}", el),
+                })
+                .collect()
+        }
+        show(&handle_text(raw.to_string()))
+    }
+
+    #[test]
+    fn emphasis_spans_inner_punctuation() {
+        assert_eq!(inline("*foo-bar*"), "b(foo-bar)");
+        assert_eq!(inline("/it's/"), "i(it's)");
+    }
+
+    #[test]
+    fn code_and_verbatim_are_kept_raw() {
+        assert_eq!(inline("call ~a.b()~ now"), "call code(a.b()) now");
+        assert_eq!(inline("=verbatim="), "verb(verbatim)");
+        assert_eq!(inline("=*not bold*="), "verb(*not bold*)");
+    }
+
+    #[test]
+    fn markers_inside_words_stay_text() {
+        assert_eq!(inline("snake_case_names"), "snake_case_names");
+        assert_eq!(inline("a*b*c and 2+3+4"), "a*b*c and 2+3+4");
+    }
+
+    #[test]
+    fn emphasis_nests() {
+        assert_eq!(inline("*bold /italic/*"), "b(bold i(italic))");
+        assert_eq!(inline("_under +strike+_"), "u(under s(strike))");
+    }
+
+    #[test]
+    fn markup_next_to_punctuation() {
+        assert_eq!(inline("(*bold*), /it/."), "(b(bold)), i(it).");
+        assert_eq!(inline("\"~code~\"; =v=!"), "\"code(code)\"; verb(v)!");
+        assert_eq!(inline("-_under_-"), "-u(under)-");
+    }
+
+    #[test]
+    fn whitespace_inside_markers_is_text() {
+        assert_eq!(inline("* not bold*"), "* not bold*");
+        assert_eq!(inline("*not bold *"), "*not bold *");
+    }
+
+    #[test]
+    fn escaped_markers_are_text() {
+        assert_eq!(inline("\\*not bold\\*"), "*not bold*");
+        assert_eq!(inline("\\*still\\* *bold*"), "*still* b(bold)");
+    }
+}
@@ -0,0 +1,36 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime};
+
+// Parses `#+date` values: org timestamps, active `<2024-05-01 Wed 10:00>` or
+// inactive `[2024-05-01 Wed]`, and ISO 8601 dates like `2024-05-01` or
+// `2024-05-01T10:00:00+02:00`. Of a range only the start is taken, times
+// with an offset are turned into UTC.
+pub fn parse_date(date: &str) -> Option<NaiveDateTime> {
+    let date = date.trim();
+    let date = match date.chars().next() {
+        Some('<') => date[1..].split('>').next()?,
+        Some('[') => date[1..].split(']').next()?,
+        _ => date,
+    };
+
+    if let Ok(d) = DateTime::parse_from_rfc3339(date) {
+        return Some(d.naive_utc());
+    }
+    for format in &["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(d) = NaiveDateTime::parse_from_str(date, format) {
+            return Some(d);
+        }
+    }
+
+    let mut words = date.split_whitespace();
+    let day = NaiveDate::parse_from_str(words.next()?, "%Y-%m-%d").ok()?;
+    let mut time = NaiveTime::MIN;
+    for word in words {
+        // day names are localized, repeaters and warnings look like `+1w`.
+        if word.chars().all(char::is_alphabetic) || word.starts_with(&['+', '-', '.'][..]) {
+            continue;
+        }
+        // `10:00-11:30` is a time range.
+        time = NaiveTime::parse_from_str(word.split('-').next()?, "%H:%M").ok()?;
+    }
+    Some(day.and_time(time))
+}
@@ -0,0 +1,56 @@
+use syntect::highlighting::ThemeSet;
+use syntect::html::{css_for_theme_with_class_style, ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+// Spans only carry classes, so pages don't depend on the theme and switching
+// it only rewrites `highlight.css`.
+const CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+pub const CODE_CLASS: &str = "hl-code";
+const LINE_NUMBER_CSS: &str = "
+.line-number {
+ display: inline-block;
+ min-width: 2.5em;
+ padding-right: 1em;
+ text-align: right;
+ opacity: 0.5;
+ user-select: none;
+}
+";
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+// Highlighted html of `code`, `None` when the language is unknown.
+pub fn highlight(code: &str, lang: &str) -> Option<String> {
+    let syntax = SYNTAX_SET.find_syntax_by_token(lang)?;
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        generator
+            .parse_html_for_line_which_includes_newline(line)
+            .ok()?;
+    }
+    Some(generator.finalize())
+}
+
+pub fn theme_css(theme: &str) -> Result<String, String> {
+    let theme = THEME_SET.themes.get(theme).ok_or_else(|| {
+        let mut themes: Vec<&String> = THEME_SET.themes.keys().collect();
+        themes.sort();
+        format!(
+            "unknown highlight_theme \"{}\", available themes: {}",
+            theme,
+            themes
+                .iter()
+                .map(|t| t.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ")
+        )
+    })?;
+    let mut css = css_for_theme_with_class_style(theme, CLASS_STYLE).map_err(|e| e.to_string())?;
+    css.push_str(LINE_NUMBER_CSS);
+    Ok(css)
+}
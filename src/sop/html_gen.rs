@@ -1,28 +1,65 @@
-use crate::sop::ast_gen::{BlockType, LinkType, ListType, OrgElement};
+use crate::sop::ast_gen::{
+    handle_text, BlockParams, BlockType, Checkbox, Footnote, HeadlineMeta, LineNumbers, LinkType,
+    ListType, OrgElement, TableAlign, TocEntry,
+};
+use crate::sop::highlight;
 
 pub fn generate_html_for_text(t: &[OrgElement]) -> String {
     let mut out = String::new();
     for e in t {
         match e {
-            OrgElement::Text(s) => out.push_str(&s.to_string()),
-            OrgElement::Bold(s) => out.push_str(&format!("<b>{}</b>", s)),
-            OrgElement::Italic(s) => out.push_str(&format!("<i>{}</i>", s)),
-            OrgElement::Underline(s) => out.push_str(&format!("<u>{}</u>", s)),
-            OrgElement::StrikeThrough(s) => out.push_str(&format!("<s>{}</s>", s)),
-            OrgElement::Code(s) => out.push_str(&format!("<code>{}</code>", s)),
+            OrgElement::Text(s) => out.push_str(&escape_html(s)),
+            OrgElement::Html(s) => out.push_str(s),
+            OrgElement::FootnoteRef { number, first, .. } => out.push_str(&format!(
+                "<sup><a{} class=\"footref\" href=\"#fn.{n}\">{n}</a></sup>",
+                if *first {
+                    format!(" id=\"fnr.{}\"", number)
+                } else {
+                    "".to_string()
+                },
+                n = number
+            )),
+            OrgElement::Bold(t) => out.push_str(&format!("<b>{}</b>", generate_html_for_text(t))),
+            OrgElement::Italic(t) => out.push_str(&format!("<i>{}</i>", generate_html_for_text(t))),
+            OrgElement::Underline(t) => {
+                out.push_str(&format!("<u>{}</u>", generate_html_for_text(t)))
+            }
+            OrgElement::StrikeThrough(t) => {
+                out.push_str(&format!("<s>{}</s>", generate_html_for_text(t)))
+            }
+            OrgElement::Code(s) => out.push_str(&format!("<code>{}</code>", escape_html(s))),
+            OrgElement::Cookie {
+                done,
+                total,
+                percent,
+            } => out.push_str(&format!(
+                "<span class=\"cookie\">[{}]</span>",
+                if !percent {
+                    format!("{}/{}", done, total)
+                } else if *total == 0 {
+                    "0%".to_string()
+                } else {
+                    format!("{}%", done * 100 / total)
+                }
+            )),
+            OrgElement::Verbatim(s) => out.push_str(&format!(
+                "<code class=\"verbatim\">{}</code>",
+                escape_html(s)
+            )),
             OrgElement::Link {
                 link_type,
                 link,
+                target,
                 desc,
             } => {
                 if link_type == &LinkType::IMG {
-                    out.push_str(&format!("<img src=\"{}\" alt=\"img\"/>", link));
+                    out.push_str(&generate_html_for_img(link, desc, &None));
                 } else {
                     out.push_str(&format!(
                         "<a href=\"{}\">{}</a>",
-                        link,
+                        escape_html(link),
                         if desc.is_empty() {
-                            link.clone()
+                            escape_html(link_text(target))
                         } else {
                             generate_html_for_text(desc)
                         }
@@ -35,21 +72,103 @@ pub fn generate_html_for_text(t: &[OrgElement]) -> String {
     out
 }
 
+// What a link without description shows, its target without the `file:`
+// or `*` that only tell org what kind of link it is.
+fn link_text(target: &str) -> &str {
+    let target = target.strip_prefix("file:").unwrap_or(target);
+    target.strip_prefix('*').unwrap_or(target)
+}
+
+fn generate_html_for_img(link: &str, desc: &[OrgElement], style: &Option<String>) -> String {
+    let alt = text_content(desc);
+    format!(
+        "<img{} src=\"{}\" alt=\"{}\"/>",
+        class_attr("", style),
+        escape_html(link),
+        if alt.is_empty() {
+            "img".to_string()
+        } else {
+            escape_html(&alt)
+        }
+    )
+}
+
+// Plain text of inline elements, for attributes like `alt`.
+pub fn text_content(texts: &[OrgElement]) -> String {
+    let mut text = String::new();
+    for e in texts {
+        match e {
+            OrgElement::Text(s) | OrgElement::Code(s) | OrgElement::Verbatim(s) => text.push_str(s),
+            OrgElement::Bold(t)
+            | OrgElement::Italic(t)
+            | OrgElement::Underline(t)
+            | OrgElement::StrikeThrough(t) => text.push_str(&text_content(t)),
+            OrgElement::Link { target, desc, .. } => {
+                if desc.is_empty() {
+                    text.push_str(link_text(target));
+                } else {
+                    text.push_str(&text_content(desc));
+                }
+            }
+            _ => (),
+        }
+    }
+    text
+}
+
 pub fn generate_html_for_headline(
     level: u8,
     id: &str,
     title: &[OrgElement],
+    meta: &HeadlineMeta,
     style: &Option<String>,
 ) -> String {
+    let mut attrs = String::new();
+    let mut props: Vec<_> = meta
+        .properties
+        .iter()
+        .filter(|(k, _)| *k != "CUSTOM_ID")
+        .collect();
+    props.sort();
+    for (key, value) in props {
+        attrs.push_str(&format!(
+            " data-{}=\"{}\"",
+            slugify(key),
+            escape_html(value)
+        ));
+    }
+
+    let mut text = String::new();
+    if let Some(todo) = &meta.todo {
+        let class = if todo == "DONE" { "done" } else { "todo" };
+        text.push_str(&format!(
+            "<span class=\"{} {t}\">{t}</span> ",
+            class,
+            t = escape_html(todo)
+        ));
+    }
+    if let Some(p) = meta.priority {
+        text.push_str(&format!(
+            "<span class=\"priority priority-{}\">[{}]</span> ",
+            p.to_ascii_lowercase(),
+            p
+        ));
+    }
+    text.push_str(&generate_html_for_text(title));
+    if !meta.tags.is_empty() {
+        text.push_str(" <span class=\"tags\">");
+        for tag in &meta.tags {
+            text.push_str(&format!("<span class=\"tag\">{}</span>", escape_html(tag)));
+        }
+        text.push_str("</span>");
+    }
+
     format!(
-        "<h{l}{} id=\"{}\">{}</h{l}>\n",
-        if let Some(s) = style {
-            format!(" class=\"{}\"", s)
-        } else {
-            "".to_string()
-        },
-        id,
-        generate_html_for_text(title),
+        "<h{l}{} id=\"{}\"{}>{}</h{l}>\n",
+        class_attr("", style),
+        escape_html(id),
+        attrs,
+        text,
         l = if level > 6 { &6u8 } else { &level }
     )
 }
@@ -57,134 +176,345 @@ pub fn generate_html_for_headline(
 pub fn generate_html_for_block(
     block_type: &BlockType,
     value: &str,
-    params: &str,
+    params: &BlockParams,
     style: &Option<String>,
 ) -> String {
     match block_type {
         BlockType::SRC => {
-            let escaped_value = value;
-            let escaped_value = &escaped_value.replace('&', "&amp;");
-            let escaped_value = &escaped_value.replace('>', "&gt;");
-            let escaped_value = &escaped_value.replace('<', "&lt;");
-            let escaped_value = &escaped_value.replace('\'', "&#39;");
-            let escaped_value = &escaped_value.replace('"', "&quot;");
+            let lang = params.lang.as_str();
+            let class = if lang.is_empty() {
+                class_attr("", style)
+            } else {
+                class_attr(&format!("language-{}", lang), style)
+            };
 
-            format!(
-                "<pre><code{}>{}</code></pre>\n",
-                if let Some(s) = style {
-                    format!(" class=\"{} {}\"", params, s)
-                } else {
-                    format!(" class=\"{}\"", params)
-                },
-                escaped_value
-            )
+            let (pre_class, code) = match highlight::highlight(value, lang) {
+                Some(highlighted) => (format!(" class=\"{}\"", highlight::CODE_CLASS), highlighted),
+                None => ("".to_string(), escape_html(value)),
+            };
+            let code = match params.line_numbers {
+                Some(LineNumbers::NEW(start)) => number_lines(&code, value.lines().count(), start),
+                _ => code,
+            };
+
+            let pre = format!("<pre{}><code{}>{}</code></pre>\n", pre_class, class, code);
+            match params.args.get("caption") {
+                Some(caption) => format!(
+                    "<figure>\n{}<figcaption>{}</figcaption>\n</figure>\n",
+                    pre,
+                    generate_html_for_text(&handle_text(caption.to_string()))
+                ),
+                None => pre,
+            }
         }
-        BlockType::QUOTE => format!(
-            "<blockquote{}>{}</blockquote>\n",
-            if let Some(s) = style {
-                format!(" class=\"{}\"", s)
-            } else {
-                "".to_string()
-            },
-            value
-        ),
+        BlockType::RESULTS => format!("<pre class=\"results\">{}</pre>\n", escape_html(value)),
         BlockType::HTML => value.to_string(),
-        BlockType::UNDEFINED => format!(
+        BlockType::EXAMPLE => format!(
             "<pre{}>{}</pre>\n",
-            if let Some(s) = style {
-                format!(" class=\"{}\"", s)
-            } else {
-                "".to_string()
-            },
-            value
+            class_attr("example", style),
+            escape_html(value)
         ),
+        BlockType::VERSE => {
+            let lines: Vec<String> = value
+                .lines()
+                .map(|l| {
+                    let text = l.trim_start();
+                    format!(
+                        "{}{}",
+                        "&nbsp;".repeat(l.len() - text.len()),
+                        generate_html_for_text(&handle_text(text.to_string()))
+                    )
+                })
+                .collect();
+            format!(
+                "<p{}>\n{}\n</p>\n",
+                class_attr("verse", style),
+                lines.join("<br/>\n")
+            )
+        }
+        BlockType::EXPORT => match params.lang.to_lowercase().as_str() {
+            "html" => value.to_string(),
+            "css" => format!("<style>\n{}\n</style>\n", value),
+            _ => String::new(),
+        },
+        // comments aren't exported, containers are rendered from their
+        // parsed contents by `generate_html_for_container`.
+        BlockType::COMMENT | BlockType::QUOTE | BlockType::CENTER | BlockType::SPECIAL(_) => {
+            String::new()
+        }
     }
 }
 
+pub fn generate_html_for_container(
+    block_type: &BlockType,
+    inner_html: &str,
+    style: &Option<String>,
+) -> String {
+    let class = match block_type {
+        BlockType::QUOTE => {
+            return format!(
+                "<blockquote{}>\n{}</blockquote>\n",
+                class_attr("", style),
+                inner_html
+            )
+        }
+        BlockType::CENTER => "center",
+        BlockType::SPECIAL(name) => name,
+        _ => "",
+    };
+    format!("<div{}>\n{}</div>\n", class_attr(class, style), inner_html)
+}
+
+pub fn class_attr(class: &str, style: &Option<String>) -> String {
+    match (class, style) {
+        ("", None) => "".to_string(),
+        ("", Some(s)) => format!(" class=\"{}\"", escape_html(s)),
+        (c, None) => format!(" class=\"{}\"", escape_html(c)),
+        (c, Some(s)) => format!(" class=\"{} {}\"", escape_html(c), escape_html(s)),
+    }
+}
+
+// Prefixes the first `lines` lines of `code` with their numbers. Spans of
+// highlighted code may cross lines, the number spans nest in them fine.
+fn number_lines(code: &str, lines: usize, start: usize) -> String {
+    code.split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if i < lines {
+                format!("<span class=\"line-number\">{}</span>{}", start + i, line)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('>', "&gt;")
+        .replace('<', "&lt;")
+        .replace('\'', "&#39;")
+        .replace('"', "&quot;")
+}
+
 pub fn generate_html_for_paragraph(el: &[OrgElement], style: &Option<String>) -> String {
     if el.len() == 1 {
         if let OrgElement::Link {
-            link_type, link, ..
+            link_type,
+            link,
+            desc,
+            ..
         } = &el[0]
         {
             if link_type == &LinkType::IMG {
-                return format!(
-                    "<img{} src=\"{}\" alt=\"img\"/>",
-                    if let Some(s) = style {
-                        format!(" class=\"{}\"", s)
-                    } else {
-                        "".to_string()
-                    },
-                    link
-                );
+                return generate_html_for_img(link, desc, style);
             }
         }
     }
     format!(
         "<p{}>{}</p>\n",
-        if let Some(s) = style {
-            format!(" class=\"{}\"", s)
-        } else {
-            "".to_string()
-        },
+        class_attr("", style),
         generate_html_for_text(el)
     )
 }
 
-pub fn generate_html_for_list(t: &ListType, els: &[OrgElement]) -> String {
+// `contents` holds the rendered contents of each item.
+pub fn generate_html_for_list(t: &ListType, items: &[OrgElement], contents: &[String]) -> String {
     let mut list_content = String::new();
-    for e in els {
-        match e {
-            OrgElement::List {
-                list_type, items, ..
-            } => {
-                list_content.push_str(&generate_html_for_list(list_type, items));
-            }
-            OrgElement::ListItem(childs, _) => {
-                list_content.push_str(&generate_html_for_list_item(childs));
-            }
-            OrgElement::Paragraph { childs, style } => {
-                list_content.push_str(&generate_html_for_paragraph(childs, style));
+    for (item, contents) in items.iter().zip(contents) {
+        if let OrgElement::ListItem {
+            childs,
+            checkbox,
+            counter,
+            term,
+            ..
+        } = item
+        {
+            if t == &ListType::DESCRIPTION {
+                // org's own export names an item without a term so.
+                let term = term
+                    .as_deref()
+                    .map_or("(no term)".to_string(), generate_html_for_text);
+                let (class, input) = checkbox_html(checkbox);
+                list_content.push_str(&format!(
+                    "<dt{}>{}{}</dt>
+",
+                    class, input, term
+                ));
+                list_content.push_str(&format!(
+                    "<dd>{}{}</dd>
+",
+                    generate_html_for_text(childs),
+                    list_item_contents(contents)
+                ));
+            } else {
+                // only an ordered list can count from `[@N]`.
+                let counter = counter.filter(|_| t == &ListType::ORDERED);
+                list_content.push_str(&generate_html_for_list_item(
+                    term, childs, checkbox, &counter, contents,
+                ));
             }
-            _ => (),
         }
     }
 
-    if t == &ListType::UNORDERED {
-        return format!("<ul>\n{}</ul>\n", list_content);
+    match t {
+        ListType::UNORDERED => format!("<ul>\n{}</ul>\n", list_content),
+        ListType::ORDERED => format!("<ol>\n{}</ol>\n", list_content),
+        ListType::DESCRIPTION => format!("<dl>\n{}</dl>\n", list_content),
     }
-    format!("<ol>\n{}</ol>\n", list_content)
 }
-pub fn generate_html_for_list_item(els: &[OrgElement]) -> String {
-    format!("<li>{}</li>\n", generate_html_for_text(&els))
+
+// `term` is the one of a `- term :: definition` item in a list that isn't a
+// description list, kept in front of its text.
+pub fn generate_html_for_list_item(
+    term: &Option<Vec<OrgElement>>,
+    els: &[OrgElement],
+    checkbox: &Option<Checkbox>,
+    counter: &Option<usize>,
+    contents: &str,
+) -> String {
+    let (class, input) = checkbox_html(checkbox);
+    format!(
+        "<li{}{}>{}{}{}{}</li>\n",
+        class,
+        counter.map_or(String::new(), |n| format!(" value=\"{}\"", n)),
+        input,
+        term.as_deref().map_or(String::new(), |t| format!(
+            "<b>{}</b> — ",
+            generate_html_for_text(t)
+        )),
+        generate_html_for_text(els),
+        list_item_contents(contents)
+    )
+}
+
+// Blocks under an item start on their own line.
+fn list_item_contents(contents: &str) -> String {
+    if contents.is_empty() {
+        String::new()
+    } else {
+        format!("\n{}", contents)
+    }
 }
-pub fn generate_html_id(texts: &[OrgElement]) -> String {
-    fn remove_spaces(s: &str) -> String {
-        s.trim()
-            .chars()
-            .map(|c| match c {
-                ' ' | '\t' => '-',
-                _ => c,
-            })
-            .collect()
-    }
-
-    let mut id = String::new();
-    use OrgElement::*;
-    for e in texts {
-        match e {
-            Text(s) | Bold(s) | Italic(s) | Underline(s) | StrikeThrough(s) | Code(s) => {
-                id.push_str(&s.to_lowercase())
-            }
-            Link { link, desc, .. } => {
-                if desc.is_empty() {
-                    id.push_str(link);
-                } else {
-                    id.push_str(&generate_html_id(desc)[..]);
-                }
+
+// The class and disabled input of an item with a checkbox.
+fn checkbox_html(checkbox: &Option<Checkbox>) -> (&'static str, &'static str) {
+    match checkbox {
+        Some(Checkbox::ON) => (
+            " class=\"on\"",
+            "<input type=\"checkbox\" checked disabled/> ",
+        ),
+        Some(Checkbox::OFF) => (" class=\"off\"", "<input type=\"checkbox\" disabled/> "),
+        Some(Checkbox::TRANS) => (" class=\"trans\"", "<input type=\"checkbox\" disabled/> "),
+        None => ("", ""),
+    }
+}
+pub fn generate_html_for_table(
+    header: &[Vec<Vec<OrgElement>>],
+    rows: &[Vec<Vec<OrgElement>>],
+    align: &[Option<TableAlign>],
+    style: &Option<String>,
+) -> String {
+    fn generate_row(cells: &[Vec<OrgElement>], align: &[Option<TableAlign>], tag: &str) -> String {
+        let mut row = String::from("<tr>");
+        for (i, cell) in cells.iter().enumerate() {
+            row.push_str(&format!(
+                "<{t}{}>{}</{t}>",
+                match align.get(i) {
+                    Some(Some(TableAlign::LEFT)) => " style=\"text-align: left\"",
+                    Some(Some(TableAlign::CENTER)) => " style=\"text-align: center\"",
+                    Some(Some(TableAlign::RIGHT)) => " style=\"text-align: right\"",
+                    _ => "",
+                },
+                generate_html_for_text(cell),
+                t = tag
+            ));
+        }
+        row.push_str("</tr>\n");
+        row
+    }
+
+    let mut table_content = String::new();
+    if !header.is_empty() {
+        table_content.push_str("<thead>\n");
+        for r in header {
+            table_content.push_str(&generate_row(r, align, "th"));
+        }
+        table_content.push_str("</thead>\n");
+    }
+    table_content.push_str("<tbody>\n");
+    for r in rows {
+        table_content.push_str(&generate_row(r, align, "td"));
+    }
+    table_content.push_str("</tbody>\n");
+
+    format!(
+        "<table{}>\n{}</table>\n",
+        class_attr("", style),
+        table_content
+    )
+}
+pub fn generate_html_for_footnotes(footnotes: &[Footnote]) -> String {
+    if footnotes.is_empty() {
+        return String::new();
+    }
+    let mut items = String::new();
+    for f in footnotes {
+        items.push_str(&format!(
+            "<li id=\"fn.{n}\">{} <a class=\"footback\" href=\"#fnr.{n}\">&#8617;</a></li>\n",
+            generate_html_for_text(&f.childs),
+            n = f.number
+        ));
+    }
+    format!("<div class=\"footnotes\">\n<ol>\n{}</ol>\n</div>\n", items)
+}
+pub fn generate_html_for_toc(entries: &[TocEntry]) -> String {
+    fn items(entries: &[TocEntry]) -> String {
+        let mut out = String::from("<ul>\n");
+        for e in entries {
+            out.push_str(&format!(
+                "<li><a href=\"#{}\">{}</a>",
+                escape_html(&e.id),
+                escape_html(&e.title)
+            ));
+            if !e.children.is_empty() {
+                out.push_str(&format!("\n{}", items(&e.children)));
             }
-            _ => (),
+            out.push_str("</li>\n");
         }
+        out.push_str("</ul>\n");
+        out
+    }
+
+    if entries.is_empty() {
+        return String::new();
+    }
+    format!("<nav class=\"toc\">\n{}</nav>\n", items(entries))
+}
+
+pub fn generate_html_id(texts: &[OrgElement]) -> String {
+    slugify(&text_content(texts))
+}
+
+// Lowercase ascii words joined by dashes; other scripts are transliterated
+// and punctuation is dropped, so the id is safe in urls as is.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in deunicode::deunicode(text).chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if (c.is_whitespace() || c == '-' || c == '_')
+            && !slug.is_empty()
+            && !slug.ends_with('-')
+        {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug.to_string()
     }
-    remove_spaces(&id.trim())
 }
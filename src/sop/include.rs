@@ -0,0 +1,280 @@
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::sop::ast_gen::ParseError;
+
+// What expanding the `#+include:` lines of a file left behind: the files it
+// depends on, problems with the directives, and where the included text
+// went, to give parse errors the line of the file they come from.
+#[derive(Debug, Default)]
+pub struct Includes {
+    pub files: Vec<PathBuf>,
+    pub errors: Vec<ParseError>,
+    // line of the expanded text an inclusion starts at, its number of lines
+    // and the line of its directive.
+    inserted: Vec<(usize, usize, usize)>,
+}
+
+impl Includes {
+    // The line of the including file `line` of the expanded text comes from,
+    // the directive's line for included text.
+    pub fn source_line(&self, line: usize) -> usize {
+        let mut shift = 0isize;
+        for (start, len, directive) in &self.inserted {
+            if line < *start {
+                break;
+            }
+            if line < start + len {
+                return *directive;
+            }
+            shift += *len as isize - 1;
+        }
+        (line as isize - shift) as usize
+    }
+}
+
+// Reads `path` with the files it includes in place of their directives:
+// `#+include: "file.org"`, `#+include: "main.rs" src rust`, with
+// `:lines "10-20"` and `:minlevel 2`. Paths are relative to the including
+// file, `/` is the site's root.
+pub fn expand(path: &Path) -> std::io::Result<(String, Includes)> {
+    let raw = fs::read_to_string(path)?;
+    let mut includes = Includes::default();
+    let mut stack = vec![normalize(path)];
+    let text = expand_str(&raw, path, &mut stack, &mut includes, None);
+    Ok((text, includes))
+}
+
+// `directive_line` is the line of the top file's directive the text is
+// included by, errors in included files are reported there.
+fn expand_str(
+    raw: &str,
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    includes: &mut Includes,
+    directive_line: Option<usize>,
+) -> String {
+    let mut text = String::new();
+    let mut expanded_line = 1;
+    for (i, line) in raw.lines().enumerate() {
+        let directive = match include_directive(line) {
+            Some(d) => d,
+            None => {
+                text.push_str(line);
+                text.push('\n');
+                expanded_line += 1;
+                continue;
+            }
+        };
+
+        // a directive that fails is left out.
+        let line = directive_line.unwrap_or(i + 1);
+        let included = include(directive, path, line, stack, includes).unwrap_or_else(|msg| {
+            includes.errors.push(ParseError {
+                line: Some(line),
+                msg: match directive_line {
+                    Some(_) => format!("in {}:{}: {}", path.display(), i + 1, msg),
+                    None => msg,
+                },
+            });
+            String::new()
+        });
+
+        let len = included.lines().count();
+        if directive_line.is_none() {
+            includes.inserted.push((expanded_line, len, i + 1));
+        }
+        expanded_line += len;
+        text.push_str(&included);
+    }
+    text
+}
+
+// The text a directive of the file at `path` stands for.
+fn include(
+    directive: &str,
+    path: &Path,
+    directive_line: usize,
+    stack: &mut Vec<PathBuf>,
+    includes: &mut Includes,
+) -> Result<String, String> {
+    let args = split_args(directive);
+    let file = match args.first() {
+        Some(f) => resolve_path(path, f),
+        None => return Err("#+include: needs a file".to_string()),
+    };
+    if stack.contains(&file) {
+        let cycle: Vec<String> = stack
+            .iter()
+            .chain(std::iter::once(&file))
+            .map(|p| p.display().to_string())
+            .collect();
+        return Err(format!("include cycle {}", cycle.join(" -> ")));
+    }
+    if !includes.files.contains(&file) {
+        includes.files.push(file.clone());
+    }
+    let content = fs::read_to_string(&file)
+        .map_err(|e| format!("can't include {}: {}", file.display(), e))?;
+
+    let mut block = None;
+    let mut lines = None;
+    let mut minlevel = None;
+    let mut rest = args[1..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            ":lines" => lines = rest.next(),
+            ":minlevel" => minlevel = rest.next().and_then(|l| l.parse::<usize>().ok()),
+            a if a.starts_with(':') => {
+                rest.next();
+            }
+            a if block.is_none() => block = Some((a, Vec::new())),
+            a => {
+                if let Some((_, params)) = &mut block {
+                    params.push(a);
+                }
+            }
+        }
+    }
+
+    let mut content = match lines {
+        Some(l) => select_lines(&content, l).ok_or(format!("invalid :lines \"{}\"", l))?,
+        None => content,
+    };
+    if !content.ends_with('\n') {
+        content.push('\n');
+    }
+
+    Ok(match block {
+        Some((block_type, params)) => format!(
+            "#+begin_{} {}\n{}#+end_{}\n",
+            block_type,
+            params.join(" "),
+            content,
+            block_type
+        ),
+        None => {
+            stack.push(file.clone());
+            let org = expand_str(&content, &file, stack, includes, Some(directive_line));
+            stack.pop();
+            match minlevel {
+                Some(level) => shift_headlines(&org, level),
+                None => org,
+            }
+        }
+    })
+}
+
+// What follows `#+include:`, if `line` is one.
+fn include_directive(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let prefix = line.get(..10)?;
+    if prefix.eq_ignore_ascii_case("#+include:") {
+        Some(line[10..].trim())
+    } else {
+        None
+    }
+}
+
+// Words of a directive, quoted ones keep their spaces.
+fn split_args(directive: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut chars = directive.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut arg = String::new();
+        if c == '"' {
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                arg.push(c);
+            }
+        } else {
+            arg.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                arg.push(c);
+            }
+        }
+        args.push(arg);
+    }
+    args
+}
+
+// `:lines "10-20"` keeps lines 10 to 19 like org does, `"-20"` and `"10-"`
+// leave the range open.
+fn select_lines(content: &str, range: &str) -> Option<String> {
+    let (start, end) = range.split_once('-')?;
+    let start = if start.is_empty() {
+        1
+    } else {
+        start.trim().parse().ok()?
+    };
+    let end = if end.is_empty() {
+        usize::MAX
+    } else {
+        end.trim().parse().ok()?
+    };
+    Some(
+        content
+            .lines()
+            .enumerate()
+            .filter(|(i, _)| i + 1 >= start && i + 1 < end)
+            .map(|(_, l)| format!("{}\n", l))
+            .collect(),
+    )
+}
+
+// Moves headlines so the highest ones are at `minlevel`.
+fn shift_headlines(org: &str, minlevel: usize) -> String {
+    let level = |l: &str| {
+        let stars = l.len() - l.trim_start_matches('*').len();
+        if stars > 0 && l[stars..].starts_with(' ') {
+            Some(stars)
+        } else {
+            None
+        }
+    };
+    let top = match org.lines().filter_map(level).min() {
+        Some(t) => t,
+        None => return org.to_string(),
+    };
+
+    let mut shifted = String::new();
+    for line in org.lines() {
+        match level(line) {
+            Some(l) => {
+                shifted.push_str(&"*".repeat(l + minlevel.max(1) - top));
+                shifted.push_str(&line[l..]);
+            }
+            None => shifted.push_str(line),
+        }
+        shifted.push('\n');
+    }
+    shifted
+}
+
+// The `./dir/file` path of `file` as written in `from`, `/` being the
+// site's root.
+pub fn resolve_path(from: &Path, file: &str) -> PathBuf {
+    match file.strip_prefix('/') {
+        Some(f) => normalize(&Path::new(".").join(f)),
+        None => normalize(&from.parent().unwrap_or(Path::new(".")).join(file)),
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from(".");
+    for c in path.components() {
+        match c {
+            Component::Normal(s) => normalized.push(s),
+            Component::ParentDir if normalized != Path::new(".") => {
+                normalized.pop();
+            }
+            _ => (),
+        }
+    }
+    normalized
+}
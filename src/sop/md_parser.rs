@@ -0,0 +1,426 @@
+use pulldown_cmark::{
+    Alignment, CodeBlockKind, Event, MetadataBlockKind, Options, Parser, Tag, TagEnd,
+};
+use regex::Regex;
+use std::collections::HashSet;
+
+use crate::sop::ast_gen::{
+    BlockParams, BlockType, Checkbox, HeadlineMeta, LinkType, ListType, OrgDoc, OrgElement,
+    ParseError, TableAlign,
+};
+use crate::sop::html_gen::generate_html_id;
+
+lazy_static! {
+    static ref REGEX_STYLE_COMMENT: Regex =
+        Regex::new(r"(?is)^\s*<!--\s*#\+style:(.*?)-->\s*$").unwrap();
+}
+
+#[derive(Debug)]
+pub struct MdParser {
+    raw_str: String,
+}
+
+impl MdParser {
+    pub fn create_from_str(raw_str: String) -> MdParser {
+        MdParser { raw_str }
+    }
+    pub fn create_from_path(path: &std::path::Path) -> std::io::Result<MdParser> {
+        Ok(MdParser::create_from_str(std::fs::read_to_string(path)?))
+    }
+    pub fn create_ast(&self) -> OrgDoc {
+        let mut doc = OrgDoc::new();
+        let mut md = MdEvents {
+            events: Parser::new_ext(&self.raw_str, md_options()),
+            styles: HashSet::new(),
+            errors: Vec::new(),
+        };
+
+        for el in md.blocks(None) {
+            doc.add_child(el);
+        }
+        for style in md.styles {
+            doc.styles.insert(style);
+        }
+        doc.errors.append(&mut md.errors);
+        doc.build_toc();
+        doc
+    }
+}
+
+fn md_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS
+}
+
+// Walks pulldown-cmark events and turns them into the same `OrgElement` tree
+// the org parser produces, so html generation and templates don't care about
+// the source format. `<!-- #+style: ... -->` comments play the role of org's
+// `#+style:` lines and apply to the following element.
+struct MdEvents<'a> {
+    events: Parser<'a>,
+    styles: HashSet<String>,
+    errors: Vec<ParseError>,
+}
+
+impl<'a> MdEvents<'a> {
+    fn blocks(&mut self, end: Option<TagEnd>) -> Vec<OrgElement> {
+        let mut els = Vec::new();
+        let mut style: Option<String> = None;
+
+        while let Some(ev) = self.events.next() {
+            match ev {
+                Event::End(e) if Some(e) == end => break,
+                Event::Start(Tag::MetadataBlock(kind)) => els.extend(self.front_matter(kind)),
+                Event::Start(Tag::HtmlBlock) => {
+                    let html = self.text_until(TagEnd::HtmlBlock);
+                    if let Some(cap) = REGEX_STYLE_COMMENT.captures(&html) {
+                        let s = cap.get(1).unwrap().as_str().trim().to_string();
+                        for val in s.split_whitespace() {
+                            self.styles.insert(val.to_string());
+                        }
+                        style = Some(s);
+                        continue;
+                    }
+                    els.push(OrgElement::Block {
+                        block_type: BlockType::HTML,
+                        params: BlockParams::default(),
+                        value: html,
+                        style: None,
+                    });
+                }
+                Event::Start(tag) => {
+                    if let Some(el) = self.block(tag, style.take()) {
+                        els.push(el);
+                    }
+                }
+                Event::Rule => els.push(OrgElement::Block {
+                    block_type: BlockType::HTML,
+                    params: BlockParams::default(),
+                    value: "<hr/>\n".to_string(),
+                    style: None,
+                }),
+                _ => (),
+            }
+        }
+        els
+    }
+
+    fn block(&mut self, tag: Tag, style: Option<String>) -> Option<OrgElement> {
+        let end = tag.to_end();
+        match tag {
+            Tag::Heading { level, .. } => {
+                let title = self.inline(end);
+                Some(OrgElement::Headline {
+                    level: level as u8,
+                    id: generate_html_id(&title),
+                    title,
+                    is_section: false,
+                    meta: HeadlineMeta::default(),
+                    style,
+                    section_style: None,
+                })
+            }
+            Tag::Paragraph => {
+                let childs = self.inline(end);
+                if childs.is_empty() {
+                    return None;
+                }
+                Some(OrgElement::Paragraph { childs, style })
+            }
+            Tag::CodeBlock(kind) => Some(OrgElement::Block {
+                block_type: BlockType::SRC,
+                params: match kind {
+                    CodeBlockKind::Fenced(lang) => BlockParams::parse(&lang),
+                    CodeBlockKind::Indented => BlockParams::default(),
+                },
+                value: self.text_until(end),
+                style,
+            }),
+            Tag::BlockQuote(_) => {
+                let childs = self.blocks(Some(end));
+                Some(OrgElement::Container {
+                    block_type: BlockType::QUOTE,
+                    childs,
+                    style,
+                })
+            }
+            Tag::List(start) => Some(self.list(start)),
+            Tag::Table(aligns) => Some(self.table(aligns, style)),
+            _ => {
+                self.blocks(Some(end));
+                None
+            }
+        }
+    }
+
+    fn list(&mut self, start: Option<u64>) -> OrgElement {
+        let mut items = Vec::new();
+        while let Some(ev) = self.events.next() {
+            match ev {
+                Event::End(TagEnd::List(_)) => break,
+                Event::Start(Tag::Item) => self.list_item(&mut items),
+                _ => (),
+            }
+        }
+
+        if let Some(OrgElement::ListItem { counter, .. }) = items.first_mut() {
+            *counter = start.filter(|n| *n != 1).map(|n| n as usize);
+        }
+
+        OrgElement::List {
+            list_type: if start.is_some() {
+                ListType::ORDERED
+            } else {
+                ListType::UNORDERED
+            },
+            items,
+        }
+    }
+
+    fn list_item(&mut self, items: &mut Vec<OrgElement>) {
+        let mut text = Vec::new();
+        let mut rest = Vec::new();
+        let mut checkbox = None;
+
+        while let Some(ev) = self.events.next() {
+            match ev {
+                Event::End(TagEnd::Item) => break,
+                Event::Start(Tag::Paragraph) => {
+                    let childs = self.inline(TagEnd::Paragraph);
+                    if text.is_empty() && rest.is_empty() {
+                        text = childs;
+                    } else {
+                        rest.push(OrgElement::Paragraph {
+                            childs,
+                            style: None,
+                        });
+                    }
+                }
+                Event::TaskListMarker(checked) => {
+                    checkbox = Some(if checked { Checkbox::ON } else { Checkbox::OFF })
+                }
+                Event::Start(Tag::List(start)) => rest.push(self.list(start)),
+                Event::Start(
+                    tag @ Tag::Heading { .. }
+                    | tag @ Tag::CodeBlock(_)
+                    | tag @ Tag::BlockQuote(_)
+                    | tag @ Tag::Table(_),
+                ) => {
+                    if let Some(el) = self.block(tag, None) {
+                        rest.push(el);
+                    }
+                }
+                ev => self.inline_event(ev, &mut text),
+            }
+        }
+
+        items.push(OrgElement::ListItem {
+            childs: text,
+            checkbox,
+            counter: None,
+            term: None,
+            contents: rest,
+        });
+    }
+
+    fn table(&mut self, aligns: Vec<Alignment>, style: Option<String>) -> OrgElement {
+        let mut header = Vec::new();
+        let mut rows = Vec::new();
+        while let Some(ev) = self.events.next() {
+            match ev {
+                Event::End(TagEnd::Table) => break,
+                Event::Start(Tag::TableHead) => header.push(self.table_row(TagEnd::TableHead)),
+                Event::Start(Tag::TableRow) => rows.push(self.table_row(TagEnd::TableRow)),
+                _ => (),
+            }
+        }
+
+        OrgElement::Table {
+            header,
+            rows,
+            align: aligns
+                .iter()
+                .map(|a| match a {
+                    Alignment::Left => Some(TableAlign::LEFT),
+                    Alignment::Center => Some(TableAlign::CENTER),
+                    Alignment::Right => Some(TableAlign::RIGHT),
+                    Alignment::None => None,
+                })
+                .collect(),
+            style,
+        }
+    }
+
+    fn table_row(&mut self, end: TagEnd) -> Vec<Vec<OrgElement>> {
+        let mut cells = Vec::new();
+        while let Some(ev) = self.events.next() {
+            match ev {
+                Event::End(e) if e == end => break,
+                Event::Start(Tag::TableCell) => cells.push(self.inline(TagEnd::TableCell)),
+                _ => (),
+            }
+        }
+        cells
+    }
+
+    fn inline(&mut self, end: TagEnd) -> Vec<OrgElement> {
+        let mut els = Vec::new();
+        while let Some(ev) = self.events.next() {
+            match ev {
+                Event::End(e) if e == end => break,
+                ev => self.inline_event(ev, &mut els),
+            }
+        }
+        els
+    }
+
+    fn inline_event(&mut self, ev: Event, els: &mut Vec<OrgElement>) {
+        match ev {
+            Event::Text(t) => push_text(els, &t),
+            Event::Code(t) => els.push(OrgElement::Code(t.to_string())),
+            Event::SoftBreak => push_text(els, " "),
+            Event::HardBreak => els.push(OrgElement::Html("<br/>".to_string())),
+            Event::InlineHtml(h) | Event::Html(h) => els.push(OrgElement::Html(h.to_string())),
+            Event::Start(tag) => {
+                let end = tag.to_end();
+                match tag {
+                    Tag::Emphasis => els.push(OrgElement::Italic(self.inline(end))),
+                    Tag::Strong => els.push(OrgElement::Bold(self.inline(end))),
+                    Tag::Strikethrough => els.push(OrgElement::StrikeThrough(self.inline(end))),
+                    Tag::Link { dest_url, .. } => els.push(OrgElement::Link {
+                        link_type: LinkType::A,
+                        link: md_link(&dest_url),
+                        target: dest_url.to_string(),
+                        desc: self.inline(end),
+                    }),
+                    Tag::Image { dest_url, .. } => {
+                        let alt = self.text_until(end);
+                        els.push(OrgElement::Link {
+                            link_type: LinkType::IMG,
+                            link: dest_url.to_string(),
+                            target: dest_url.to_string(),
+                            desc: if alt.is_empty() {
+                                Vec::new()
+                            } else {
+                                vec![OrgElement::Text(alt)]
+                            },
+                        });
+                    }
+                    _ => els.append(&mut self.inline(end)),
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn text_until(&mut self, end: TagEnd) -> String {
+        let mut text = String::new();
+        for ev in self.events.by_ref() {
+            match ev {
+                Event::End(e) if e == end => break,
+                Event::Text(t) | Event::Code(t) | Event::Html(t) | Event::InlineHtml(t) => {
+                    text.push_str(&t)
+                }
+                Event::SoftBreak | Event::HardBreak => text.push(' '),
+                _ => (),
+            }
+        }
+        text
+    }
+
+    // Front matter keys are handed to `OrgDoc` as keywords, so `title: Foo`
+    // behaves exactly like `#+title: Foo` in an org file.
+    fn front_matter(&mut self, kind: MetadataBlockKind) -> Vec<OrgElement> {
+        let text = self.text_until(TagEnd::MetadataBlock(kind));
+        let mut keywords = Vec::new();
+
+        match kind {
+            MetadataBlockKind::PlusesStyle => match toml::from_str::<toml::value::Table>(&text) {
+                Ok(t) => {
+                    for (k, v) in t {
+                        let value = match v {
+                            toml::Value::String(s) => s,
+                            toml::Value::Array(a) => a
+                                .iter()
+                                .map(|v| v.as_str().map(|s| s.to_string()).unwrap_or(v.to_string()))
+                                .collect::<Vec<String>>()
+                                .join(" "),
+                            v => v.to_string(),
+                        };
+                        keywords.push(create_front_matter_keyword(&k, &value));
+                    }
+                }
+                Err(e) => self.errors.push(ParseError {
+                    // front matter starts after the opening `+++` line
+                    line: Some(e.line_col().map_or(1, |(l, _)| l + 2)),
+                    msg: format!("front matter can't be parsed. Error: {}", e),
+                }),
+            },
+            MetadataBlockKind::YamlStyle => {
+                for line in text.lines() {
+                    if line.starts_with(' ') || line.trim_start().starts_with('#') {
+                        continue;
+                    }
+                    if let Some(i) = line.find(':') {
+                        let value = line.get(i + 1..).unwrap().trim();
+                        let value = if value.starts_with('[') && value.ends_with(']') {
+                            value
+                                .get(1..value.len() - 1)
+                                .unwrap()
+                                .split(',')
+                                .map(unquote)
+                                .collect::<Vec<&str>>()
+                                .join(" ")
+                        } else {
+                            unquote(value).to_string()
+                        };
+                        keywords.push(create_front_matter_keyword(line.get(..i).unwrap(), &value));
+                    }
+                }
+            }
+        }
+        keywords
+    }
+}
+
+fn create_front_matter_keyword(key: &str, value: &str) -> OrgElement {
+    OrgElement::Keyword {
+        key: key.trim().to_uppercase(),
+        value: value.trim().to_string(),
+    }
+}
+
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    if s.len() >= 2
+        && ((s.starts_with('"') && s.ends_with('"')) || (s.starts_with('\'') && s.ends_with('\'')))
+    {
+        return s.get(1..s.len() - 1).unwrap();
+    }
+    s
+}
+
+fn push_text(els: &mut Vec<OrgElement>, t: &str) {
+    if let Some(OrgElement::Text(s)) = els.last_mut() {
+        s.push_str(t);
+    } else {
+        els.push(OrgElement::Text(t.to_string()));
+    }
+}
+
+fn md_link(dest_url: &str) -> String {
+    if dest_url.contains("://") {
+        return dest_url.to_string();
+    }
+    let (path, anchor) = match dest_url.find('#') {
+        Some(i) => dest_url.split_at(i),
+        None => (dest_url, ""),
+    };
+    match path.strip_suffix(".md") {
+        Some(p) => format!("{}.html{}", p, anchor),
+        None => dest_url.to_string(),
+    }
+}
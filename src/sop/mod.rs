@@ -1,3 +1,7 @@
 pub mod ast_gen;
+pub mod date;
+pub mod highlight;
 mod html_gen;
+pub mod include;
+pub mod md_parser;
 pub mod parser;
@@ -1,28 +1,47 @@
 use crate::sop::html_gen::*;
 use crate::sop::{
-    ast_gen, ast_gen::handle_section_style, ast_gen::handle_style, ast_gen::OrgDoc,
-    ast_gen::OrgElement,
+    ast_gen, ast_gen::handle_caption, ast_gen::handle_section_style, ast_gen::handle_style,
+    ast_gen::Footnote, ast_gen::OrgDoc, ast_gen::OrgElement, ast_gen::TocEntry, include,
+    include::Includes,
 };
 
 #[derive(Debug)]
 pub struct OrgParser {
     raw_str: String,
+    includes: Includes,
 }
 
 impl OrgParser {
     pub fn create_from_str(raw_str: String) -> OrgParser {
-        OrgParser { raw_str }
+        OrgParser {
+            raw_str,
+            includes: Includes::default(),
+        }
     }
-    pub fn create_from_path(path: &std::path::Path) -> OrgParser {
-        OrgParser::create_from_str(
-            std::fs::read_to_string(path)
-                .expect("Something went wrong reading the file. Check path, permissions etc.."),
-        )
+    // Reads the file with its `#+include:` directives expanded.
+    pub fn create_from_path(path: &std::path::Path) -> std::io::Result<OrgParser> {
+        let (raw_str, includes) = include::expand(path)?;
+        Ok(OrgParser { raw_str, includes })
     }
     pub fn create_ast(&self) -> OrgDoc {
+        let mut doc = self.parse();
+        for e in &mut doc.errors {
+            e.line = e.line.map(|l| self.includes.source_line(l));
+        }
+        doc.errors.extend(self.includes.errors.iter().cloned());
+        doc.includes = self.includes.files.clone();
+        doc.number_footnotes();
+        doc.update_cookies();
+        doc.build_toc();
+        doc
+    }
+
+    fn parse(&self) -> OrgDoc {
         let mut doc = OrgDoc::new();
 
-        for cap in ast_gen::REGEX_ALL.captures_iter(&self.raw_str) {
+        let mut pos = 0;
+        while let Some(cap) = ast_gen::REGEX_ALL.captures_at(&self.raw_str, pos) {
+            pos = cap.get(0).unwrap().end();
             if let Some(c) = cap.name("headline") {
                 doc.handle_undetect_str(c.start(), c.end(), &self.raw_str);
                 doc.add_child(ast_gen::create_headline(
@@ -36,74 +55,221 @@ impl OrgParser {
                 doc.add_child(ast_gen::create_keyword(c.as_str()));
             }
             if let Some(c) = cap.name("list") {
-                doc.handle_undetect_str(c.start(), c.end(), &self.raw_str);
-                doc.add_child(ast_gen::create_list_item(c.as_str()));
+                let end = ast_gen::list_end(&self.raw_str, c.start());
+                doc.handle_undetect_str(c.start(), end, &self.raw_str);
+                let line = self.raw_str[..c.start()].matches('\n').count() + 1;
+                let list = OrgParser::create_list(&mut doc, &self.raw_str[c.start()..end], line);
+                doc.add_child(list);
+                pos = end;
             }
-            if let Some(c) = cap.name("block") {
+            if let Some(c) = cap.name("table") {
                 doc.handle_undetect_str(c.start(), c.end(), &self.raw_str);
-                doc.add_child(ast_gen::create_block(
+                doc.add_child(ast_gen::create_table(
                     c.as_str(),
                     handle_style(c.start(), &self.raw_str),
                 ));
             }
+            if let Some(c) = cap.name("block") {
+                let end = ast_gen::block_end(&self.raw_str, c.start()).unwrap_or(c.end());
+                doc.handle_undetect_str(c.start(), end, &self.raw_str);
+                let block = ast_gen::create_block(
+                    &self.raw_str[c.start()..end],
+                    handle_style(c.start(), &self.raw_str),
+                    handle_caption(c.start(), &self.raw_str),
+                );
+                let line = self.raw_str[..c.start()].matches('\n').count() + 1;
+                let block = OrgParser::create_container(&mut doc, block, line);
+                doc.add_child(block);
+                pos = end;
+            }
+            if let Some(c) = cap.name("footnote") {
+                let end = ast_gen::footnote_end(&self.raw_str, c.start());
+                doc.handle_undetect_str(c.start(), end, &self.raw_str);
+                doc.add_child(ast_gen::create_footnote(&self.raw_str[c.start()..end]));
+                pos = end;
+            }
+            if let Some(c) = cap.name("results") {
+                doc.handle_undetect_str(c.start(), c.end(), &self.raw_str);
+                if doc.results_exported() {
+                    doc.add_child(ast_gen::create_results(c.as_str()));
+                }
+            }
         }
 
         doc.handle_undetect_str(self.raw_str.len(), self.raw_str.len(), &self.raw_str);
         doc
     }
+    // Parses the contents of blocks like `#+begin_note` into a subtree, their
+    // styles, errors and footnotes count for the page.
+    fn create_container(doc: &mut OrgDoc, block: OrgElement, line: usize) -> OrgElement {
+        match block {
+            OrgElement::Block {
+                block_type,
+                value,
+                style,
+                ..
+            } if block_type.is_container() => {
+                let nested = OrgParser::create_from_str(value).parse();
+                let childs = doc.add_nested(nested, line);
+                OrgElement::Container {
+                    block_type,
+                    childs,
+                    style,
+                }
+            }
+            block => block,
+        }
+    }
+    // Parses what each item of a list starting at `line` holds, its first
+    // paragraph becomes the item's text.
+    fn create_list(doc: &mut OrgDoc, raw: &str, line: usize) -> OrgElement {
+        let mut items = Vec::new();
+        for (offset, raw_item) in ast_gen::split_list(raw) {
+            let (mut item, body) = ast_gen::create_list_item(raw_item);
+            let nested = OrgParser::create_from_str(body).parse();
+            let mut nested_childs = doc.add_nested(nested, line + offset - 1);
+            if let OrgElement::ListItem {
+                childs, contents, ..
+            } = &mut item
+            {
+                if let Some(OrgElement::Paragraph { .. }) = nested_childs.first() {
+                    if let OrgElement::Paragraph { childs: text, .. } = nested_childs.remove(0) {
+                        *childs = text;
+                    }
+                }
+                *contents = nested_childs;
+            }
+            items.push(item);
+        }
+        ast_gen::create_list(raw, items)
+    }
+    pub fn generate_footnotes(footnotes: &[Footnote]) -> String {
+        generate_html_for_footnotes(footnotes)
+    }
+    pub fn generate_toc(toc: &[TocEntry]) -> String {
+        generate_html_for_toc(toc)
+    }
     #[allow(dead_code)]
     pub fn create_html(&self) -> String {
         OrgParser::generate_html(&self.create_ast().ast)
     }
 
     pub fn generate_html(section: &OrgElement) -> String {
+        match section {
+            OrgElement::Section { childs, .. } => OrgParser::generate_html_for_childs(childs),
+            _ => String::new(),
+        }
+    }
+
+    fn generate_html_for_childs(childs: &[OrgElement]) -> String {
         let mut out_html = String::new();
 
-        if let OrgElement::Section { childs, .. } = section {
-            for el in childs {
-                match el {
-                    OrgElement::Headline {
-                        level,
-                        id,
-                        title,
-                        style,
-                        ..
-                    } => {
-                        out_html.push_str(&generate_html_for_headline(*level, id, title, style));
-                    }
-                    OrgElement::Block {
+        for el in childs {
+            match el {
+                OrgElement::Headline {
+                    level,
+                    id,
+                    title,
+                    meta,
+                    style,
+                    ..
+                } => {
+                    out_html.push_str(&generate_html_for_headline(*level, id, title, meta, style));
+                }
+                OrgElement::Block {
+                    block_type,
+                    value,
+                    params,
+                    style,
+                } => {
+                    out_html.push_str(&generate_html_for_block(block_type, value, params, style));
+                }
+                OrgElement::Container {
+                    block_type,
+                    childs,
+                    style,
+                } => {
+                    out_html.push_str(&generate_html_for_container(
                         block_type,
-                        value,
-                        params,
+                        &OrgParser::generate_html_for_childs(childs),
                         style,
-                    } => {
-                        out_html
-                            .push_str(&generate_html_for_block(block_type, value, params, style));
-                    }
-                    OrgElement::List {
-                        list_type, items, ..
-                    } => {
-                        out_html.push_str(&generate_html_for_list(list_type, items));
-                    }
-                    OrgElement::Paragraph { childs, style } => {
-                        out_html.push_str(&generate_html_for_paragraph(childs, style));
-                    }
-                    OrgElement::Section { style, .. } => {
-                        out_html.push_str(&format!(
-                            "<div {}>\n{}</div>\n",
-                            if let Some(s) = style {
-                                format!("class=\"{}\"", s)
-                            } else {
-                                "".to_string()
-                            },
-                            OrgParser::generate_html(el)
-                        ));
-                    }
-                    _ => (),
+                    ));
+                }
+                OrgElement::List { list_type, items } => {
+                    let contents: Vec<String> = items
+                        .iter()
+                        .map(|item| match item {
+                            OrgElement::ListItem { contents, .. } => {
+                                OrgParser::generate_html_for_childs(contents)
+                            }
+                            _ => String::new(),
+                        })
+                        .collect();
+                    out_html.push_str(&generate_html_for_list(list_type, items, &contents));
+                }
+                OrgElement::Toc { entries, .. } => {
+                    out_html.push_str(&generate_html_for_toc(entries));
+                }
+                OrgElement::Paragraph { childs, style } => {
+                    out_html.push_str(&generate_html_for_paragraph(childs, style));
                 }
+                OrgElement::Table {
+                    header,
+                    rows,
+                    align,
+                    style,
+                } => {
+                    out_html.push_str(&generate_html_for_table(header, rows, align, style));
+                }
+                OrgElement::Section { style, .. } => {
+                    out_html.push_str(&format!(
+                        "<div{}>\n{}</div>\n",
+                        class_attr("", style),
+                        OrgParser::generate_html(el)
+                    ));
+                }
+                _ => (),
             }
         }
 
         out_html
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(org: &str) -> String {
+        OrgParser::create_from_str(org.to_string()).create_html()
+    }
+
+    #[test]
+    fn mixed_description_list_keeps_terms() {
+        let out = html("- plain\n- term :: def\n");
+        assert!(out.contains("<ul>"), "{}", out);
+        assert!(out.contains("<li><b>term</b> — def</li>"), "{}", out);
+
+        let out = html("- term :: def\n- plain\n");
+        assert!(out.contains("<dl>"), "{}", out);
+        assert!(
+            out.contains("<dt>(no term)</dt>\n<dd>plain</dd>"),
+            "{}",
+            out
+        );
+    }
+
+    #[test]
+    fn headline_titles_are_trimmed() {
+        let doc = OrgParser::create_from_str("* TODO [#A] Progress [1/2] :tag:\n".to_string())
+            .create_ast();
+        assert_eq!(doc.anchors[0].title, "Progress");
+        assert_eq!(doc.toc[0].title, "Progress");
+    }
+
+    #[test]
+    fn counter_only_in_ordered_lists() {
+        assert!(html("1. [@3] three\n").contains("<li value=\"3\">"));
+        assert!(!html("- [@3] three\n").contains("value="));
+    }
+}